@@ -9,11 +9,11 @@ use streamcraft::{
         audiodecoder::AudioDecoder, demuxsrc::DemuxSrc, videodecoder::VideoDecoder,
         ResourceLocation,
     },
-    pipeline::Pipeline,
+    pipeline::{graph::PipelineGraph, Pipeline},
 };
 
 fn main() {
-    let mut demuxer = DemuxSrc::new(ResourceLocation::new_file(PathBuf::from(
+    let demuxer = DemuxSrc::new(ResourceLocation::new_file(PathBuf::from(
         "/home/merb/Videos/tran.mp4",
     )))
     .unwrap();
@@ -21,14 +21,14 @@ fn main() {
     let vdecoder = VideoDecoder::new(demuxer.get_video_stream().unwrap()).unwrap();
     let adecoder = AudioDecoder::new(demuxer.get_audio_stream().unwrap()).unwrap();
 
-    demuxer
-        .link_video_sink_element(vdecoder.get_stream_index(), vdecoder)
-        .unwrap();
-    demuxer
-        .link_audio_sink_element(adecoder.get_stream_index(), adecoder)
-        .unwrap();
+    let mut graph = PipelineGraph::new();
+    let demuxer = graph.add_element(demuxer);
+    let vdecoder = graph.add_element(vdecoder);
+    let adecoder = graph.add_element(adecoder);
+    graph.link((demuxer, "video"), (vdecoder, "sink")).unwrap();
+    graph.link((demuxer, "audio"), (adecoder, "sink")).unwrap();
 
-    let mut pipeline = Pipeline::new(demuxer);
+    let mut pipeline = Pipeline::new(graph);
     pipeline.init().unwrap();
 
     for _ in 0..15 {
@@ -4,16 +4,19 @@
 
 use streamcraft::{
     elements::text::{stdoutlog::StdoutLog, texttestsrc::TextTestSrc},
-    pipeline::Pipeline,
+    pipeline::{graph::PipelineGraph, Pipeline},
 };
 
 fn main() {
-    let stdoutlog = StdoutLog::new();
     let mut texttest = TextTestSrc::new();
-    texttest.link_sink_element(stdoutlog).unwrap();
     texttest.set_text_to_send("Hello, World!\n".to_string());
 
-    let mut pipeline = Pipeline::new(texttest);
+    let mut graph = PipelineGraph::new();
+    let texttest = graph.add_element(texttest);
+    let stdoutlog = graph.add_element(StdoutLog::new());
+    graph.link((texttest, "src"), (stdoutlog, "sink")).unwrap();
+
+    let mut pipeline = Pipeline::new(graph);
     pipeline.init().unwrap();
 
     pipeline.iter().unwrap();
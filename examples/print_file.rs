@@ -3,21 +3,25 @@
 // Use StreamCraft to print the contents of a text file
 
 use streamcraft::{
-    elements::conversion::bytes2text::Bytes2Text, elements::io::filesrc::FileSrc,
-    elements::text::stdoutlog::StdoutLog, pipeline::Pipeline,
+    elements::conversion::bytes2text::Bytes2Text,
+    elements::io::filesrc::FileSrc,
+    elements::text::stdoutlog::StdoutLog,
+    pipeline::{graph::PipelineGraph, Pipeline},
 };
 
 fn main() {
-    let stdoutlog = StdoutLog::new();
-    let mut bytes2text = Bytes2Text::new();
-    bytes2text.link_sink_element(stdoutlog).unwrap();
-
     let f = std::fs::File::open("README.md").unwrap();
-    let mut filesrc = FileSrc::new(f);
-    filesrc.link_sink_element(bytes2text).unwrap();
 
-    let mut pipeline = Pipeline::new(filesrc);
-    pipeline.init().unwrap();
+    let mut graph = PipelineGraph::new();
+    let filesrc = graph.add_element(FileSrc::new(f));
+    let bytes2text = graph.add_element(Bytes2Text::new());
+    let stdoutlog = graph.add_element(StdoutLog::new());
+    graph.link((filesrc, "src"), (bytes2text, "sink")).unwrap();
+    graph
+        .link((bytes2text, "src"), (stdoutlog, "sink"))
+        .unwrap();
 
-    while pipeline.iter().is_ok() {}
+    let mut pipeline = Pipeline::new(graph);
+    pipeline.play().unwrap();
+    pipeline.wait_eos().unwrap();
 }
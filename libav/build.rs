@@ -33,18 +33,29 @@ fn main() {
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .allowlist_function("av_find_input_format")
         .allowlist_function("avformat_open_input")
+        .allowlist_function("avformat_find_stream_info")
         .allowlist_function("avformat_version")
         .allowlist_function("av_read_frame")
         .allowlist_function("avformat_close_input")
         .allowlist_function("avformat_alloc_context")
         .allowlist_function("av_packet_alloc")
         .allowlist_function("av_packet_unref")
+        .allowlist_function("av_packet_free")
+        .allowlist_function("av_packet_ref")
         .allowlist_function("av_find_best_stream")
         .allowlist_function("avcodec_find_decoder")
         .allowlist_function("avcodec_alloc_context3")
         .allowlist_function("avcodec_find_decoder")
         .allowlist_function("avcodec_free_context")
         .allowlist_function("avcodec_parameters_to_context")
+        .allowlist_function("avcodec_parameters_alloc")
+        .allowlist_function("avcodec_parameters_free")
+        .allowlist_function("avcodec_parameters_copy")
+        .allowlist_function("avcodec_get_type")
+        .allowlist_function("av_mallocz")
+        .allowlist_function("av_freep")
+        .allowlist_function("av_channel_layout_default")
+        .allowlist_function("av_channel_layout_uninit")
         .allowlist_function("avcodec_open2")
         .allowlist_function("avcodec_send_packet")
         .allowlist_function("avcodec_receive_packet")
@@ -52,7 +63,24 @@ fn main() {
         .allowlist_function("avcodec_receive_frame")
         .allowlist_function("av_frame_alloc")
         .allowlist_function("av_frame_free")
+        .allowlist_function("av_frame_ref")
         .allowlist_function("avcodec_find_encoder_by_name")
+        .allowlist_function("avformat_seek_file")
+        .allowlist_function("avcodec_flush_buffers")
+        .allowlist_function("avcodec_version")
+        .allowlist_function("av_codec_iterate")
+        .allowlist_function("av_codec_is_decoder")
+        .allowlist_function("av_codec_is_encoder")
+        .allowlist_function("av_demuxer_iterate")
+        .allowlist_function("av_strerror")
+        .allowlist_function("av_dict_get")
+        .allowlist_function("avcodec_get_name")
+        .allowlist_function("av_get_pix_fmt_name")
+        .allowlist_function("av_image_fill_plane_sizes")
+        .allowlist_function("av_get_sample_fmt_name")
+        .allowlist_function("av_sample_fmt_is_planar")
+        .allowlist_function("av_get_bytes_per_sample")
+        .allowlist_function("av_channel_layout_describe")
         .allowlist_type("AVInputFormat")
         .allowlist_type("AVFormatContext")
         .allowlist_type("AVPacket")
@@ -61,6 +89,21 @@ fn main() {
         .allowlist_type("AVFrame")
         .allowlist_var("sc_libav_averror_eof")
         .allowlist_var("sc_libav_averror_eagain")
+        .allowlist_var("sc_libav_averror_einval")
+        .allowlist_var("sc_libav_averror_enomem")
+        .allowlist_var("sc_libav_averror_enoent")
+        .allowlist_var("sc_libav_averror_eacces")
+        .allowlist_var("sc_libav_averror_invaliddata")
+        .allowlist_var("sc_libav_averror_decoder_not_found")
+        .allowlist_var("sc_libav_av_nopts_value")
+        .allowlist_var("AV_TIME_BASE")
+        .allowlist_var("AV_INPUT_BUFFER_PADDING_SIZE")
+        .allowlist_var("AV_DISPOSITION_.*")
+        .allowlist_var("AV_PKT_FLAG_KEY")
+        .allowlist_var("AV_PKT_FLAG_CORRUPT")
+        .allowlist_var("AV_FRAME_FLAG_KEY")
+        .allowlist_var("AV_FRAME_FLAG_INTERLACED")
+        .allowlist_var("AV_FRAME_FLAG_TOP_FIELD_FIRST")
         // Finish the builder and generate the bindings.
         .generate()
         // Unwrap the Result and panic on failure.
@@ -8,8 +8,10 @@ fn main() {
     )))
     .unwrap();
 
-    let video_decoder = Decoder::new(demuxer.get_video_stream().unwrap());
-    let audio_decoder = Decoder::new(demuxer.get_audio_stream().unwrap());
+    let video_stream = demuxer.get_video_stream().unwrap();
+    let video_decoder = Decoder::new(video_stream.info.index, &video_stream.params);
+    let audio_stream = demuxer.get_audio_stream().unwrap();
+    let audio_decoder = Decoder::new(audio_stream.info.index, &audio_stream.params);
 
     // demuxer.read_frame().unwrap();
 }
@@ -15,6 +15,116 @@
 
 use crate::bindings;
 
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+/// Version of libavformat.
 pub fn version() -> u32 {
     unsafe { bindings::avformat_version() }
 }
+
+/// Split a version from [`version`] into major, minor and micro.
+pub fn version_triple(version: u32) -> (u32, u32, u32) {
+    (version >> 16, (version >> 8) & 0xff, version & 0xff)
+}
+
+/// Version of libavcodec.
+pub fn codec_version() -> u32 {
+    unsafe { bindings::avcodec_version() }
+}
+
+pub(crate) fn to_string(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MediaType {
+    Video,
+    Audio,
+    Subtitle,
+    /// Opaque data, e.g. timecodes.
+    Data,
+    /// Files that come with the input, e.g. fonts for subtitles.
+    Attachment,
+    Other,
+}
+
+impl MediaType {
+    pub(crate) fn from_av(media_type: bindings::AVMediaType) -> Self {
+        match media_type {
+            bindings::AVMediaType_AVMEDIA_TYPE_VIDEO => Self::Video,
+            bindings::AVMediaType_AVMEDIA_TYPE_AUDIO => Self::Audio,
+            bindings::AVMediaType_AVMEDIA_TYPE_SUBTITLE => Self::Subtitle,
+            bindings::AVMediaType_AVMEDIA_TYPE_DATA => Self::Data,
+            bindings::AVMediaType_AVMEDIA_TYPE_ATTACHMENT => Self::Attachment,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A codec libav was built with.
+#[derive(Clone, Debug)]
+pub struct CodecInfo {
+    pub name: String,
+    pub long_name: String,
+    pub media_type: MediaType,
+    pub decoder: bool,
+    pub encoder: bool,
+}
+
+/// A demuxer libav was built with.
+#[derive(Clone, Debug)]
+pub struct DemuxerInfo {
+    /// Comma separated short names, e.g. `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub name: String,
+    pub long_name: String,
+}
+
+/// Every codec libav provides. Decoders and encoders with the same name are listed separately.
+pub fn codecs() -> Vec<CodecInfo> {
+    let mut codecs = Vec::new();
+    let mut opaque: *mut c_void = std::ptr::null_mut();
+    loop {
+        let codec = unsafe { bindings::av_codec_iterate(&mut opaque) };
+        if codec.is_null() {
+            break;
+        }
+
+        unsafe {
+            codecs.push(CodecInfo {
+                name: to_string((*codec).name),
+                long_name: to_string((*codec).long_name),
+                media_type: MediaType::from_av((*codec).type_),
+                decoder: bindings::av_codec_is_decoder(codec) != 0,
+                encoder: bindings::av_codec_is_encoder(codec) != 0,
+            });
+        }
+    }
+
+    codecs
+}
+
+/// Every demuxer libav provides.
+pub fn demuxers() -> Vec<DemuxerInfo> {
+    let mut demuxers = Vec::new();
+    let mut opaque: *mut c_void = std::ptr::null_mut();
+    loop {
+        let demuxer = unsafe { bindings::av_demuxer_iterate(&mut opaque) };
+        if demuxer.is_null() {
+            break;
+        }
+
+        unsafe {
+            demuxers.push(DemuxerInfo {
+                name: to_string((*demuxer).name),
+                long_name: to_string((*demuxer).long_name),
+            });
+        }
+    }
+
+    demuxers
+}
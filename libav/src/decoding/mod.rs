@@ -13,13 +13,26 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{bindings, demuxing::Packet, error::Error};
+use std::{ffi::CStr, os::raw::c_char};
+
+use crate::{
+    bindings,
+    demuxing::Packet,
+    error::{AvError, Error, ErrorKind},
+};
 
 // TODO: Move somewhre else
+/// Decoded video or audio. The data is reference counted, [`Frame::try_clone`] adds a
+/// reference to it instead of copying it.
 pub struct Frame {
-    pub inner: *mut bindings::AVFrame,
+    pub(crate) inner: *mut bindings::AVFrame,
 }
 
+// The data is never changed through a shared reference and libav counts the references to it
+// atomically
+unsafe impl Send for Frame {}
+unsafe impl Sync for Frame {}
+
 impl Drop for Frame {
     fn drop(&mut self) {
         unsafe {
@@ -41,8 +54,171 @@ impl Frame {
         }
     }
 
-    pub fn get_pts(&self) -> i64 {
-        unsafe { (*self.inner).pts }
+    /// A new reference to the data of this frame.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let frame = Self::new()?;
+        AvError::check(unsafe { bindings::av_frame_ref(frame.inner, self.inner) })
+            .map_err(Error::FailedToReferenceFrame)?;
+
+        Ok(frame)
+    }
+
+    /// Presentation timestamp in the time base of the stream, `None` if unknown.
+    pub fn pts(&self) -> Option<i64> {
+        unsafe {
+            let pts = (*self.inner).pts;
+            (pts != bindings::sc_libav_av_nopts_value).then_some(pts)
+        }
+    }
+
+    /// Duration in the time base of the stream, `None` if unknown.
+    pub fn duration(&self) -> Option<i64> {
+        let duration = unsafe { (*self.inner).duration };
+        (duration > 0).then_some(duration)
+    }
+
+    pub fn width(&self) -> u32 {
+        unsafe { (*self.inner).width.max(0) as u32 }
+    }
+
+    pub fn height(&self) -> u32 {
+        unsafe { (*self.inner).height.max(0) as u32 }
+    }
+
+    /// Name of the pixel format, e.g. `yuv420p`, `None` if the frame is not video.
+    pub fn pixel_format(&self) -> Option<String> {
+        unsafe {
+            let name = bindings::av_get_pix_fmt_name((*self.inner).format);
+            if name.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(name).to_string_lossy().into_owned())
+        }
+    }
+
+    /// The planes of a video frame as data and stride in bytes. Empty if the frame is not
+    /// video or is stored bottom up.
+    pub fn video_planes(&self) -> Vec<(&[u8], usize)> {
+        unsafe {
+            let frame = &*self.inner;
+            let mut linesizes = [0isize; 4];
+            for (linesize, stride) in linesizes.iter_mut().zip(frame.linesize) {
+                *linesize = stride as isize;
+            }
+
+            let mut sizes = [0usize; 4];
+            let ret = bindings::av_image_fill_plane_sizes(
+                sizes.as_mut_ptr(),
+                frame.format,
+                frame.height,
+                linesizes.as_ptr(),
+            );
+            if ret < 0 {
+                return Vec::new();
+            }
+
+            sizes
+                .iter()
+                .zip(frame.data)
+                .zip(frame.linesize)
+                .take_while(|((size, data), _)| **size > 0 && !data.is_null())
+                .map(|((size, data), stride)| {
+                    (std::slice::from_raw_parts(data, *size), stride as usize)
+                })
+                .collect()
+        }
+    }
+
+    /// Samples per second of an audio frame.
+    pub fn sample_rate(&self) -> u32 {
+        unsafe { (*self.inner).sample_rate.max(0) as u32 }
+    }
+
+    /// Samples per channel of an audio frame.
+    pub fn samples(&self) -> usize {
+        unsafe { (*self.inner).nb_samples.max(0) as usize }
+    }
+
+    pub fn channels(&self) -> u32 {
+        unsafe { (*self.inner).ch_layout.nb_channels.max(0) as u32 }
+    }
+
+    /// Description of the channel layout, e.g. `stereo` or `5.1`, `None` if the frame is not
+    /// audio.
+    pub fn channel_layout(&self) -> Option<String> {
+        let mut buf = [0 as c_char; 128];
+        unsafe {
+            if bindings::av_channel_layout_describe(
+                &(*self.inner).ch_layout,
+                buf.as_mut_ptr(),
+                buf.len(),
+            ) < 0
+            {
+                return None;
+            }
+
+            Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Name of the sample format, e.g. `s16` or `fltp`, `None` if the frame is not audio.
+    pub fn sample_format(&self) -> Option<String> {
+        unsafe {
+            let name = bindings::av_get_sample_fmt_name((*self.inner).format);
+            if name.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(name).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Whether every channel of an audio frame is in its own plane.
+    pub fn is_planar(&self) -> bool {
+        unsafe { bindings::av_sample_fmt_is_planar((*self.inner).format) != 0 }
+    }
+
+    /// The planes of an audio frame, one per channel if the frame is planar, otherwise one with
+    /// the samples of all channels interleaved. Empty if the frame is not audio.
+    pub fn audio_planes(&self) -> Vec<&[u8]> {
+        unsafe {
+            let frame = &*self.inner;
+            let bytes_per_sample = bindings::av_get_bytes_per_sample(frame.format);
+            if bytes_per_sample <= 0 || frame.extended_data.is_null() {
+                return Vec::new();
+            }
+
+            let channels = self.channels() as usize;
+            let (planes, size) = if self.is_planar() {
+                (channels, self.samples() * bytes_per_sample as usize)
+            } else {
+                (1, self.samples() * bytes_per_sample as usize * channels)
+            };
+
+            (0..planes)
+                .map(|plane| *frame.extended_data.add(plane))
+                .take_while(|data| !data.is_null())
+                .map(|data| std::slice::from_raw_parts(data, size))
+                .collect()
+        }
+    }
+
+    pub fn is_keyframe(&self) -> bool {
+        self.has_flag(bindings::AV_FRAME_FLAG_KEY)
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.has_flag(bindings::AV_FRAME_FLAG_INTERLACED)
+    }
+
+    /// Whether the top field is displayed first, only meaningful for interlaced frames.
+    pub fn is_top_field_first(&self) -> bool {
+        self.has_flag(bindings::AV_FRAME_FLAG_TOP_FIELD_FIRST)
+    }
+
+    fn has_flag(&self, flag: u32) -> bool {
+        unsafe { (*self.inner).flags as u32 & flag != 0 }
     }
 }
 
@@ -63,53 +239,90 @@ impl Drop for Decoder {
 }
 
 impl Decoder {
-    pub fn new(
-        (stream_index, codec_id, params): (i32, crate::demuxing::CodecID, crate::demuxing::CodecParams),
-    ) -> Result<Self, Error> {
-        let decoder = unsafe { bindings::avcodec_find_decoder(codec_id) };
+    /// A decoder for the packets of the stream at `stream_index`, any stream if it is negative.
+    pub fn new(stream_index: i32, params: &crate::demuxing::CodecParams) -> Result<Self, Error> {
+        let codec_id = params.codec_id();
+        let decoder_ptr = unsafe { bindings::avcodec_find_decoder(codec_id) };
 
-        if decoder.is_null() {
-            return Err(Error::FailedToFindDecoder);
+        if decoder_ptr.is_null() {
+            return Err(Error::FailedToFindDecoder(codec_id));
         }
 
-        let decoder_ctx = unsafe { bindings::avcodec_alloc_context3(decoder) };
+        let decoder_ctx = unsafe { bindings::avcodec_alloc_context3(decoder_ptr) };
 
         if decoder_ctx.is_null() {
-            return Err(Error::FailedToCreateDecoder);
+            return Err(Error::FailedToCreateDecoder(codec_id));
         }
 
-        if unsafe { bindings::avcodec_parameters_to_context(decoder_ctx, &params.inner) } < 0 {
-            return Err(Error::FailedToCopyCodecParamsToDecoder);
-        }
-
-        if unsafe { bindings::avcodec_open2(decoder_ctx, decoder, std::ptr::null_mut()) } < 0 {
-            return Err(Error::FailedToOpenCodec);
-        }
-
-        Ok(Self {
+        // Owned from here on so that the context is freed on error
+        let decoder = Self {
             stream_index,
             ctx: decoder_ctx,
-        })
+        };
+
+        let ret = unsafe { bindings::avcodec_parameters_to_context(decoder.ctx, params.inner) };
+        AvError::check(ret)
+            .map_err(|error| Error::FailedToCopyCodecParamsToDecoder { codec_id, error })?;
+
+        let ret =
+            unsafe { bindings::avcodec_open2(decoder.ctx, decoder_ptr, std::ptr::null_mut()) };
+        AvError::check(ret).map_err(|error| Error::FailedToOpenCodec { codec_id, error })?;
+
+        Ok(decoder)
+    }
+
+    /// Drop everything the decoder has buffered, e.g. after seeking.
+    pub fn flush(&self) {
+        unsafe {
+            bindings::avcodec_flush_buffers(self.ctx);
+        }
     }
 
+    /// Decode `packet`, which must be from the stream the decoder was made for, unless the
+    /// stream index was negative.
     pub fn decode_packet(&self, packet: Packet) -> Result<Vec<Frame>, Error> {
-        assert_ne!(packet.stream_index(), self.stream_index);
+        if self.stream_index >= 0 && packet.stream_index() != self.stream_index {
+            return Err(Error::PacketFromOtherStream {
+                expected: self.stream_index,
+                got: packet.stream_index(),
+            });
+        }
+
+        AvError::check(unsafe { bindings::avcodec_send_packet(self.ctx, packet.inner) })
+            .map_err(Error::FailedToSendPacketToDecoder)?;
+
+        self.receive_frames()
+    }
 
-        if unsafe { bindings::avcodec_send_packet(self.ctx, packet.inner) } < 0 {
-            return Err(Error::FailedToSendPacketToDecoder);
+    /// Get the frames the decoder is still holding back at the end of the stream, e.g. because
+    /// of frame reordering. The decoder is flushed afterwards so it can be used again.
+    pub fn drain(&self) -> Result<Vec<Frame>, Error> {
+        // Sending no packet puts the decoder in draining mode
+        let ret = unsafe { bindings::avcodec_send_packet(self.ctx, std::ptr::null()) };
+        match AvError::check(ret) {
+            Ok(_) => {}
+            // Already draining
+            Err(e) if e.kind == ErrorKind::Eof => {}
+            Err(e) => return Err(Error::FailedToSendPacketToDecoder(e)),
         }
 
+        let frames = self.receive_frames();
+        self.flush();
+
+        frames
+    }
+
+    /// Receive frames until the decoder needs more input or has nothing more to give.
+    fn receive_frames(&self) -> Result<Vec<Frame>, Error> {
         let mut frames = Vec::new();
         loop {
             let frame = Frame::new()?;
 
             let ret = unsafe { bindings::avcodec_receive_frame(self.ctx, frame.inner) };
-            if ret < 0 {
-                if ret == bindings::sc_libav_averror_eof || ret == bindings::sc_libav_averror_eagain {
-                    break;
-                }
-
-                return Err(Error::FailedToReceiveDecodedFrame);
+            match AvError::check(ret) {
+                Ok(_) => {}
+                Err(e) if matches!(e.kind, ErrorKind::Eof | ErrorKind::Again) => break,
+                Err(e) => return Err(Error::FailedToReceiveDecodedFrame(e)),
             }
 
             frames.push(frame);
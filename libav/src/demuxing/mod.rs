@@ -13,12 +13,17 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{bindings, error::Error};
+use crate::{
+    bindings,
+    core::{self, MediaType},
+    error::{AvError, Error},
+};
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use std::ffi::CString;
+use std::ffi::{c_void, CString};
 
+#[derive(Clone)]
 pub enum ResourceLocation {
     File(PathBuf),
 }
@@ -37,46 +42,408 @@ impl std::string::ToString for ResourceLocation {
     }
 }
 
-// HACK: wtf is this
-#[derive(PartialEq, Debug, Clone)]
+/// Compressed data of one stream. The data is reference counted, [`Packet::try_clone`] adds a
+/// reference to it instead of copying it.
 pub struct Packet {
-    pub inner: *mut bindings::AVPacket,
+    pub(crate) inner: *mut bindings::AVPacket,
 }
 
+// The data is never changed through a shared reference and libav counts the references to it
+// atomically
 unsafe impl Send for Packet {}
 unsafe impl Sync for Packet {}
 
+/// The kind of [`Packet::side_data`], e.g. new extradata or the display matrix.
+pub type PacketSideDataType = bindings::AVPacketSideDataType;
+
 impl Packet {
     pub fn new() -> Result<Self, Error> {
         unsafe {
             let inner = bindings::av_packet_alloc();
 
             if inner.is_null() {
-                return Err(Error::FailedToAllocPacket)
+                return Err(Error::FailedToAllocPacket);
             }
 
             Ok(Self { inner })
         }
     }
 
+    /// A new reference to the data of this packet.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let packet = Self::new()?;
+        AvError::check(unsafe { bindings::av_packet_ref(packet.inner, self.inner) })
+            .map_err(Error::FailedToReferencePacket)?;
+
+        Ok(packet)
+    }
+
     #[inline(always)]
     pub fn stream_index(&self) -> i32 {
         unsafe { (*self.inner).stream_index }
     }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            let packet = &*self.inner;
+            if packet.data.is_null() || packet.size <= 0 {
+                return &[];
+            }
+
+            std::slice::from_raw_parts(packet.data, packet.size as usize)
+        }
+    }
+
+    /// Size of the data in bytes.
+    pub fn size(&self) -> usize {
+        unsafe { (*self.inner).size.max(0) as usize }
+    }
+
+    /// Presentation timestamp in the time base of the stream, `None` if unknown.
+    pub fn pts(&self) -> Option<i64> {
+        unsafe {
+            let pts = (*self.inner).pts;
+            (pts != bindings::sc_libav_av_nopts_value).then_some(pts)
+        }
+    }
+
+    /// Decoding timestamp in the time base of the stream, `None` if unknown.
+    pub fn dts(&self) -> Option<i64> {
+        unsafe {
+            let dts = (*self.inner).dts;
+            (dts != bindings::sc_libav_av_nopts_value).then_some(dts)
+        }
+    }
+
+    /// Duration in the time base of the stream, `None` if unknown.
+    pub fn duration(&self) -> Option<i64> {
+        let duration = unsafe { (*self.inner).duration };
+        (duration > 0).then_some(duration)
+    }
+
+    /// The `AV_PKT_FLAG_*` flags.
+    pub fn flags(&self) -> i32 {
+        unsafe { (*self.inner).flags }
+    }
+
+    pub fn is_keyframe(&self) -> bool {
+        self.flags() as u32 & bindings::AV_PKT_FLAG_KEY != 0
+    }
+
+    /// Whether the demuxer found the data to be corrupt.
+    pub fn is_corrupt(&self) -> bool {
+        self.flags() as u32 & bindings::AV_PKT_FLAG_CORRUPT != 0
+    }
+
+    /// Data that comes with the packet besides the compressed data, e.g. new extradata.
+    pub fn side_data(&self) -> Vec<(PacketSideDataType, &[u8])> {
+        unsafe {
+            let packet = &*self.inner;
+            if packet.side_data.is_null() {
+                return Vec::new();
+            }
+
+            std::slice::from_raw_parts(packet.side_data, packet.side_data_elems.max(0) as usize)
+                .iter()
+                .map(|side_data| {
+                    let data = if side_data.data.is_null() {
+                        &[][..]
+                    } else {
+                        std::slice::from_raw_parts(side_data.data, side_data.size)
+                    };
+                    (side_data.type_, data)
+                })
+                .collect()
+        }
+    }
+}
+
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.stream_index() == other.stream_index()
+            && self.pts() == other.pts()
+            && self.dts() == other.dts()
+            && self.flags() == other.flags()
+            && self.data() == other.data()
+    }
+}
+
+impl std::fmt::Debug for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Packet")
+            .field("stream_index", &self.stream_index())
+            .field("pts", &self.pts())
+            .field("dts", &self.dts())
+            .field("duration", &self.duration())
+            .field("size", &self.size())
+            .field("flags", &self.flags())
+            .finish()
+    }
 }
 
 impl Drop for Packet {
     fn drop(&mut self) {
         unsafe {
-            bindings::av_packet_unref(self.inner);
+            bindings::av_packet_free(&mut self.inner);
         }
     }
 }
 
 pub type CodecID = bindings::AVCodecID;
 
+/// What a decoder needs to know about a stream, e.g. the dimensions of a video or the
+/// extradata of the codec. A copy is owned, so it stays valid after the [`Demuxer`] is gone.
 pub struct CodecParams {
-    pub inner: bindings::AVCodecParameters,
+    pub(crate) inner: *mut bindings::AVCodecParameters,
+}
+
+unsafe impl Send for CodecParams {}
+unsafe impl Sync for CodecParams {}
+
+impl Drop for CodecParams {
+    fn drop(&mut self) {
+        unsafe {
+            bindings::avcodec_parameters_free(&mut self.inner);
+        }
+    }
+}
+
+impl CodecParams {
+    /// Parameters for `codec_id` with everything else unknown.
+    pub fn new(codec_id: CodecID) -> Result<Self, Error> {
+        let params = Self::alloc()?;
+        unsafe {
+            (*params.inner).codec_id = codec_id;
+            (*params.inner).codec_type = bindings::avcodec_get_type(codec_id);
+        }
+
+        Ok(params)
+    }
+
+    fn alloc() -> Result<Self, Error> {
+        let inner = unsafe { bindings::avcodec_parameters_alloc() };
+        if inner.is_null() {
+            return Err(Error::FailedToAllocCodecParams);
+        }
+
+        Ok(Self { inner })
+    }
+
+    fn copy_from(params: *const bindings::AVCodecParameters) -> Result<Self, Error> {
+        let copy = Self::alloc()?;
+        AvError::check(unsafe { bindings::avcodec_parameters_copy(copy.inner, params) })
+            .map_err(Error::FailedToCopyCodecParams)?;
+
+        Ok(copy)
+    }
+
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        Self::copy_from(self.inner)
+    }
+
+    pub fn codec_id(&self) -> CodecID {
+        unsafe { (*self.inner).codec_id }
+    }
+
+    pub fn media_type(&self) -> MediaType {
+        MediaType::from_av(unsafe { (*self.inner).codec_type })
+    }
+
+    /// Data the codec needs to start decoding, e.g. the SPS and PPS of H.264 in MP4.
+    pub fn extradata(&self) -> &[u8] {
+        unsafe {
+            let params = &*self.inner;
+            if params.extradata.is_null() || params.extradata_size <= 0 {
+                return &[];
+            }
+
+            std::slice::from_raw_parts(params.extradata, params.extradata_size as usize)
+        }
+    }
+
+    pub fn set_extradata(&mut self, extradata: &[u8]) -> Result<(), Error> {
+        unsafe {
+            let params = &mut *self.inner;
+            bindings::av_freep(&mut params.extradata as *mut *mut u8 as *mut c_void);
+            params.extradata_size = 0;
+            if extradata.is_empty() {
+                return Ok(());
+            }
+
+            // libav reads past the end, so the padding must be there and zeroed
+            let data = bindings::av_mallocz(
+                extradata.len() + bindings::AV_INPUT_BUFFER_PADDING_SIZE as usize,
+            ) as *mut u8;
+            if data.is_null() {
+                return Err(Error::FailedToAllocCodecParams);
+            }
+            std::ptr::copy_nonoverlapping(extradata.as_ptr(), data, extradata.len());
+            params.extradata = data;
+            params.extradata_size = extradata.len() as i32;
+        }
+
+        Ok(())
+    }
+
+    /// The libav pixel format of video or sample format of audio, `None` if unknown.
+    pub fn format(&self) -> Option<i32> {
+        let format = unsafe { (*self.inner).format };
+        (format >= 0).then_some(format)
+    }
+
+    pub fn set_format(&mut self, format: i32) {
+        unsafe { (*self.inner).format = format }
+    }
+
+    /// Name of the pixel format of video, e.g. `yuv420p`.
+    pub fn pixel_format(&self) -> Option<String> {
+        if self.media_type() != MediaType::Video {
+            return None;
+        }
+
+        let name = core::to_string(unsafe { bindings::av_get_pix_fmt_name(self.format()?) });
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Name of the sample format of audio, e.g. `fltp`.
+    pub fn sample_format(&self) -> Option<String> {
+        if self.media_type() != MediaType::Audio {
+            return None;
+        }
+
+        let name = core::to_string(unsafe { bindings::av_get_sample_fmt_name(self.format()?) });
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Width and height of video in pixels, 0 if unknown.
+    pub fn dimensions(&self) -> (u32, u32) {
+        unsafe {
+            let params = &*self.inner;
+            (params.width.max(0) as u32, params.height.max(0) as u32)
+        }
+    }
+
+    pub fn set_dimensions(&mut self, width: u32, height: u32) {
+        unsafe {
+            (*self.inner).width = width as i32;
+            (*self.inner).height = height as i32;
+        }
+    }
+
+    /// Samples per second of audio, 0 if unknown.
+    pub fn sample_rate(&self) -> u32 {
+        unsafe { (*self.inner).sample_rate.max(0) as u32 }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        unsafe { (*self.inner).sample_rate = sample_rate as i32 }
+    }
+
+    /// Number of audio channels, 0 if unknown.
+    pub fn channels(&self) -> u32 {
+        unsafe { (*self.inner).ch_layout.nb_channels.max(0) as u32 }
+    }
+
+    /// Set the number of channels, in the default layout for that many channels.
+    pub fn set_channels(&mut self, channels: u32) {
+        unsafe {
+            bindings::av_channel_layout_uninit(&mut (*self.inner).ch_layout);
+            bindings::av_channel_layout_default(&mut (*self.inner).ch_layout, channels as i32);
+        }
+    }
+
+    /// Bits per second, 0 if unknown.
+    pub fn bit_rate(&self) -> i64 {
+        unsafe { (*self.inner).bit_rate }
+    }
+
+    pub fn set_bit_rate(&mut self, bit_rate: i64) {
+        unsafe { (*self.inner).bit_rate = bit_rate }
+    }
+
+    /// Bytes per audio packet for codecs with fixed size packets, 0 if unknown.
+    pub fn block_align(&self) -> u32 {
+        unsafe { (*self.inner).block_align.max(0) as u32 }
+    }
+
+    pub fn set_block_align(&mut self, block_align: u32) {
+        unsafe { (*self.inner).block_align = block_align as i32 }
+    }
+
+    /// Bits per sample in the coded data, 0 if unknown.
+    pub fn bits_per_coded_sample(&self) -> u32 {
+        unsafe { (*self.inner).bits_per_coded_sample.max(0) as u32 }
+    }
+
+    pub fn set_bits_per_coded_sample(&mut self, bits: u32) {
+        unsafe { (*self.inner).bits_per_coded_sample = bits as i32 }
+    }
+}
+
+/// How a stream is meant to be used, from the `AV_DISPOSITION_*` flags.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Disposition {
+    /// Selected when the user picks no stream of this type.
+    pub default: bool,
+    pub dub: bool,
+    pub original: bool,
+    /// E.g. a director's commentary.
+    pub comment: bool,
+    /// Subtitles that should be shown even if subtitles are off, e.g. for foreign dialogue.
+    pub forced: bool,
+    pub hearing_impaired: bool,
+    pub visual_impaired: bool,
+    /// A single picture, e.g. cover art, instead of a video.
+    pub attached_pic: bool,
+}
+
+impl Disposition {
+    fn from_flags(flags: i32) -> Self {
+        let has = |flag: u32| flags as u32 & flag != 0;
+        Self {
+            default: has(bindings::AV_DISPOSITION_DEFAULT),
+            dub: has(bindings::AV_DISPOSITION_DUB),
+            original: has(bindings::AV_DISPOSITION_ORIGINAL),
+            comment: has(bindings::AV_DISPOSITION_COMMENT),
+            forced: has(bindings::AV_DISPOSITION_FORCED),
+            hearing_impaired: has(bindings::AV_DISPOSITION_HEARING_IMPAIRED),
+            visual_impaired: has(bindings::AV_DISPOSITION_VISUAL_IMPAIRED),
+            attached_pic: has(bindings::AV_DISPOSITION_ATTACHED_PIC),
+        }
+    }
+}
+
+/// A stream of the input of a [`Demuxer`].
+#[derive(Clone, Debug)]
+pub struct StreamInfo {
+    /// Index of the stream, as in [`Packet::stream_index`].
+    pub index: i32,
+    pub media_type: MediaType,
+    pub codec_id: CodecID,
+    /// Short name of the codec, e.g. `h264`.
+    pub codec_name: String,
+    /// Numerator and denominator of the unit of the timestamps.
+    pub time_base: Option<(i32, i32)>,
+    /// `None` if unknown.
+    pub duration: Option<Duration>,
+    /// Average frames per second as numerator and denominator, video only.
+    pub frame_rate: Option<(i32, i32)>,
+    /// Width and height in pixels, video only.
+    pub dimensions: Option<(u32, u32)>,
+    /// Samples per second, audio only.
+    pub sample_rate: Option<u32>,
+    /// Audio only.
+    pub channels: Option<u32>,
+    /// ISO 639-2 code of the language, e.g. `eng`.
+    pub language: Option<String>,
+    pub disposition: Disposition,
+}
+
+/// A stream of a [`Demuxer`] with what it takes to decode it, see
+/// [`crate::decoding::Decoder::new`].
+pub struct Stream {
+    pub info: StreamInfo,
+    pub params: CodecParams,
 }
 
 pub struct Demuxer {
@@ -90,66 +457,191 @@ impl Demuxer {
     pub fn new(rl: ResourceLocation) -> Result<Self, Error> {
         let mut inner = std::ptr::null_mut();
 
-        let uri = CString::new(rl.to_string()).unwrap();
+        let uri = rl.to_string();
+        let Ok(c_uri) = CString::new(uri.clone()) else {
+            return Err(Error::InvalidUri(uri));
+        };
         let ret = unsafe {
             bindings::avformat_open_input(
                 &mut inner,
-                uri.as_ptr(),
+                c_uri.as_ptr(),
                 std::ptr::null_mut(),
                 std::ptr::null_mut(),
             )
         };
 
-        if ret < 0 {
+        if let Err(error) = AvError::check(ret) {
             unsafe {
                 bindings::avformat_close_input(&mut inner);
             }
-            return Err(Error::FailedToOpenInput);
+            return Err(Error::FailedToOpenInput { uri, error });
+        }
+
+        // Some formats only have the codec parameters of their streams in the packets
+        let ret = unsafe { bindings::avformat_find_stream_info(inner, std::ptr::null_mut()) };
+        if let Err(error) = AvError::check(ret) {
+            unsafe {
+                bindings::avformat_close_input(&mut inner);
+            }
+            return Err(Error::FailedToFindStreamInfo { uri, error });
         }
 
         Ok(Self { inner })
     }
 
-    // TODO: Return struct
-    fn find_stream(
-        &self,
-        type_: bindings::AVMediaType,
-    ) -> Result<(i32, CodecID, CodecParams), Error> {
+    fn stream_ptr(&self, stream_index: i32) -> Option<*mut bindings::AVStream> {
+        unsafe {
+            if stream_index < 0 || stream_index as u32 >= (*self.inner).nb_streams {
+                return None;
+            }
+
+            Some(*(*self.inner).streams.wrapping_add(stream_index as usize))
+        }
+    }
+
+    /// Every stream of the input in order of their index.
+    pub fn streams(&self) -> Vec<StreamInfo> {
+        let nb_streams = unsafe { (*self.inner).nb_streams };
+        (0..nb_streams as i32)
+            .filter_map(|index| self.stream_info(index))
+            .collect()
+    }
+
+    /// Information about the stream at `stream_index`, `None` if there is no such stream.
+    pub fn stream_info(&self, stream_index: i32) -> Option<StreamInfo> {
+        let stream = self.stream_ptr(stream_index)?;
+        unsafe {
+            let stream = &*stream;
+            let params = &*stream.codecpar;
+            let media_type = MediaType::from_av(params.codec_type);
+            let time_base = self.time_base(stream_index);
+
+            let duration = time_base.and_then(|(num, den)| {
+                if stream.duration == bindings::sc_libav_av_nopts_value || stream.duration < 0 {
+                    return None;
+                }
+
+                let nanos = stream.duration as u128 * num as u128 * 1_000_000_000 / den as u128;
+                Some(Duration::from_nanos(nanos as u64))
+            });
+
+            let frame_rate = (media_type == MediaType::Video
+                && stream.avg_frame_rate.num > 0
+                && stream.avg_frame_rate.den > 0)
+                .then_some((stream.avg_frame_rate.num, stream.avg_frame_rate.den));
+            let dimensions = (params.width > 0 && params.height > 0)
+                .then_some((params.width as u32, params.height as u32));
+            let sample_rate = (params.sample_rate > 0).then_some(params.sample_rate as u32);
+            let channels =
+                (params.ch_layout.nb_channels > 0).then_some(params.ch_layout.nb_channels as u32);
+
+            let key = CString::new("language").unwrap();
+            let entry = bindings::av_dict_get(stream.metadata, key.as_ptr(), std::ptr::null(), 0);
+            let language = (!entry.is_null()).then(|| core::to_string((*entry).value));
+
+            Some(StreamInfo {
+                index: stream_index,
+                media_type,
+                codec_id: params.codec_id,
+                codec_name: core::to_string(bindings::avcodec_get_name(params.codec_id)),
+                time_base,
+                duration,
+                frame_rate,
+                dimensions,
+                sample_rate,
+                channels,
+                language,
+                disposition: Disposition::from_flags(stream.disposition),
+            })
+        }
+    }
+
+    /// The stream at `stream_index` with its codec parameters, e.g. to decode a stream picked
+    /// from [`Demuxer::streams`].
+    pub fn get_stream(&self, stream_index: i32) -> Result<Stream, Error> {
+        let (Some(stream), Some(info)) = (
+            self.stream_ptr(stream_index),
+            self.stream_info(stream_index),
+        ) else {
+            return Err(Error::NoSuchStream(stream_index));
+        };
+
+        let params = CodecParams::copy_from(unsafe { (*stream).codecpar })?;
+        Ok(Stream { info, params })
+    }
+
+    fn find_stream(&self, type_: bindings::AVMediaType) -> Result<Stream, Error> {
         unsafe {
             let ret =
                 bindings::av_find_best_stream(self.inner, type_, -1, -1, std::ptr::null_mut(), 0);
 
-            if ret < 0 {
-                return Err(Error::FailedToFindBestStream);
+            let stream_index = AvError::check(ret).map_err(Error::FailedToFindBestStream)?;
+            self.get_stream(stream_index)
+        }
+    }
+
+    pub fn get_video_stream(&self) -> Result<Stream, Error> {
+        self.find_stream(bindings::AVMediaType_AVMEDIA_TYPE_VIDEO)
+    }
+
+    pub fn get_audio_stream(&self) -> Result<Stream, Error> {
+        self.find_stream(bindings::AVMediaType_AVMEDIA_TYPE_AUDIO)
+    }
+
+    /// Duration of the input, `None` if unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        unsafe {
+            let duration = (*self.inner).duration;
+            if duration == bindings::sc_libav_av_nopts_value || duration < 0 {
+                return None;
             }
 
-            let stream_index = ret;
-            let params = (*(*(*self.inner).streams.wrapping_add(stream_index as usize))).codecpar;
-            let codec_id = (*params).codec_id;
+            Some(Duration::from_micros(
+                duration as u64 * 1_000_000 / bindings::AV_TIME_BASE as u64,
+            ))
+        }
+    }
+
+    /// The time base of the stream at `stream_index` as numerator and denominator.
+    pub fn time_base(&self, stream_index: i32) -> Option<(i32, i32)> {
+        let stream = self.stream_ptr(stream_index)?;
+        unsafe {
+            let time_base = (*stream).time_base;
+            if time_base.num <= 0 || time_base.den <= 0 {
+                return None;
+            }
 
-            Ok((stream_index, codec_id, CodecParams {inner: *params}))
+            Some((time_base.num, time_base.den))
         }
     }
 
-    pub fn get_video_stream(
-        &self,
-    ) -> Result<(i32, CodecID, CodecParams), Error> {
-        self.find_stream(bindings::AVMediaType_AVMEDIA_TYPE_VIDEO)
+    /// Presentation time of `packet`, `None` if unknown.
+    pub fn packet_time(&self, packet: &Packet) -> Option<Duration> {
+        let pts = packet.pts()?;
+        let (num, den) = self.time_base(packet.stream_index())?;
+        if pts < 0 {
+            return None;
+        }
+
+        let nanos = pts as u128 * num as u128 * 1_000_000_000 / den as u128;
+        Some(Duration::from_nanos(nanos as u64))
     }
 
-    pub fn get_audio_stream(
-        &self,
-    ) -> Result<(i32, CodecID, CodecParams), Error> {
-        self.find_stream(bindings::AVMediaType_AVMEDIA_TYPE_AUDIO)
+    /// Seek to the last keyframe at or before `position` in all streams.
+    pub fn seek(&self, position: Duration) -> Result<(), Error> {
+        let ts = (position.as_micros() * bindings::AV_TIME_BASE as u128 / 1_000_000) as i64;
+        let ret = unsafe { bindings::avformat_seek_file(self.inner, -1, i64::MIN, ts, ts, 0) };
+        AvError::check(ret).map_err(Error::FailedToSeek)?;
+
+        Ok(())
     }
 
     pub fn read_frame(&self) -> Result<Packet, Error> {
         unsafe {
             let packet = Packet::new()?;
 
-            if bindings::av_read_frame(self.inner, packet.inner) < 0 {
-                return Err(Error::FailedToReadFrame);
-            }
+            AvError::check(bindings::av_read_frame(self.inner, packet.inner))
+                .map_err(Error::FailedToReadFrame)?;
 
             Ok(packet)
         }
@@ -172,4 +664,12 @@ mod tests {
     fn test_new_packet() {
         let packet = Packet::new();
     }
+
+    #[test]
+    fn test_invalid_uri() {
+        assert!(matches!(
+            Demuxer::new(ResourceLocation::new_file("a\0b".into())),
+            Err(Error::InvalidUri(_))
+        ));
+    }
 }
@@ -26,13 +26,12 @@ pub enum Codec {
 
 impl Codec {
     pub fn to_cstring(&self) -> Result<CString, Error> {
-        Ok(CString::new(
-            match self {
-                Codec::H264 => "libx264",
-                Codec::H265 => "libx265",
-                Codec::Opus => "libopus",
-            }
-        ).unwrap()) // TODO: Return appropriate error
+        Ok(CString::new(match self {
+            Codec::H264 => "libx264",
+            Codec::H265 => "libx265",
+            Codec::Opus => "libopus",
+        })
+        .unwrap()) // TODO: Return appropriate error
     }
 }
 
@@ -62,9 +61,7 @@ impl Encoder {
                 panic!("Could not open codec");
             }
 
-            Ok(Self {
-                ctx
-            })
+            Ok(Self { ctx })
         }
     }
 
@@ -80,7 +77,8 @@ impl Encoder {
             loop {
                 let packet = Packet::new().unwrap();
                 let ret = bindings::avcodec_receive_packet(self.ctx, packet.inner);
-                if ret == bindings::sc_libav_averror_eagain || ret == bindings::sc_libav_averror_eof {
+                if ret == bindings::sc_libav_averror_eagain || ret == bindings::sc_libav_averror_eof
+                {
                     break;
                 } else if ret < 0 {
                     panic!("Error during encoding");
@@ -13,43 +13,197 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::ffi::CStr;
 use std::fmt::{self, Display, Formatter};
+use std::os::raw::c_char;
+
+use crate::{bindings, demuxing::CodecID};
+
+/// The well known codes a libav function can fail with.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ErrorKind {
+    /// End of the input.
+    Eof,
+    /// Not possible right now, try again, e.g. after sending more data to a decoder.
+    Again,
+    InvalidArgument,
+    OutOfMemory,
+    /// The input is not valid, e.g. a corrupt or unknown container.
+    InvalidData,
+    DecoderNotFound,
+    /// The file or resource does not exist.
+    NotFound,
+    PermissionDenied,
+    Other,
+}
+
+/// A negative code returned by a libav function and the message libav has for it.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct AvError {
+    pub kind: ErrorKind,
+    pub code: i32,
+    pub message: String,
+}
+
+impl AvError {
+    /// Translate the return value `code` of a libav function, which must be negative.
+    pub fn from_code(code: i32) -> Self {
+        let mut buf = [0 as c_char; 128];
+        // The codes are only known at runtime and can not be matched on directly
+        let (kind, message) = unsafe {
+            let kind = match code {
+                c if c == bindings::sc_libav_averror_eof => ErrorKind::Eof,
+                c if c == bindings::sc_libav_averror_eagain => ErrorKind::Again,
+                c if c == bindings::sc_libav_averror_einval => ErrorKind::InvalidArgument,
+                c if c == bindings::sc_libav_averror_enomem => ErrorKind::OutOfMemory,
+                c if c == bindings::sc_libav_averror_invaliddata => ErrorKind::InvalidData,
+                c if c == bindings::sc_libav_averror_decoder_not_found => {
+                    ErrorKind::DecoderNotFound
+                }
+                c if c == bindings::sc_libav_averror_enoent => ErrorKind::NotFound,
+                c if c == bindings::sc_libav_averror_eacces => ErrorKind::PermissionDenied,
+                _ => ErrorKind::Other,
+            };
+
+            let message = if bindings::av_strerror(code, buf.as_mut_ptr(), buf.len()) < 0 {
+                format!("Unknown error {code}")
+            } else {
+                CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+            };
+
+            (kind, message)
+        };
+
+        Self {
+            kind,
+            code,
+            message,
+        }
+    }
+
+    /// `Err` with the error for `ret` if it is negative, otherwise `Ok` with `ret`.
+    pub(crate) fn check(ret: i32) -> Result<i32, Self> {
+        if ret < 0 {
+            return Err(Self::from_code(ret));
+        }
+
+        Ok(ret)
+    }
+}
+
+impl Display for AvError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
-    FailedToOpenInput,
-    FailedToFindBestStream,
-    FailedToReadFrame,
-    FailedToFindDecoder,
-    FailedToCreateDecoder,
-    FailedToCopyCodecParamsToDecoder,
-    FailedToOpenCodec,
+    InvalidUri(String),
+    FailedToOpenInput { uri: String, error: AvError },
+    FailedToFindStreamInfo { uri: String, error: AvError },
+    FailedToFindBestStream(AvError),
+    NoSuchStream(i32),
+    FailedToReadFrame(AvError),
+    FailedToFindDecoder(CodecID),
+    FailedToCreateDecoder(CodecID),
+    FailedToCopyCodecParamsToDecoder { codec_id: CodecID, error: AvError },
+    FailedToOpenCodec { codec_id: CodecID, error: AvError },
     FailedToAllocFrame,
     FailedToAllocPacket,
-    FailedToSendPacketToDecoder,
-    FailedToReceiveDecodedFrame,
+    FailedToAllocCodecParams,
+    FailedToCopyCodecParams(AvError),
+    FailedToReferenceFrame(AvError),
+    FailedToReferencePacket(AvError),
+    PacketFromOtherStream { expected: i32, got: i32 },
+    FailedToSendPacketToDecoder(AvError),
+    FailedToReceiveDecodedFrame(AvError),
+    FailedToSeek(AvError),
+}
+
+impl Error {
+    /// The code libav failed with, if the error came from a libav function that returns one.
+    pub fn av_error(&self) -> Option<&AvError> {
+        match self {
+            Self::FailedToOpenInput { error, .. }
+            | Self::FailedToFindStreamInfo { error, .. }
+            | Self::FailedToCopyCodecParamsToDecoder { error, .. }
+            | Self::FailedToOpenCodec { error, .. }
+            | Self::FailedToFindBestStream(error)
+            | Self::FailedToReferenceFrame(error)
+            | Self::FailedToReferencePacket(error)
+            | Self::FailedToCopyCodecParams(error)
+            | Self::FailedToReadFrame(error)
+            | Self::FailedToSendPacketToDecoder(error)
+            | Self::FailedToReceiveDecodedFrame(error)
+            | Self::FailedToSeek(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::FailedToFindDecoder(_) => ErrorKind::DecoderNotFound,
+            Self::NoSuchStream(_) => ErrorKind::NotFound,
+            Self::InvalidUri(_) | Self::PacketFromOtherStream { .. } => ErrorKind::InvalidArgument,
+            Self::FailedToAllocFrame
+            | Self::FailedToAllocPacket
+            | Self::FailedToAllocCodecParams => ErrorKind::OutOfMemory,
+            e => e.av_error().map_or(ErrorKind::Other, |error| error.kind),
+        }
+    }
+
+    /// Whether the end of the input was reached.
+    pub fn is_eof(&self) -> bool {
+        self.kind() == ErrorKind::Eof
+    }
 }
 
 impl std::error::Error for Error {}
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::FailedToOpenInput => "Failed to open input",
-                Self::FailedToFindBestStream => "Failed to find best stream",
-                Self::FailedToReadFrame => "Failed to read frame",
-                Self::FailedToFindDecoder => "Failed to find decoder",
-                Self::FailedToCreateDecoder => "Failed to create decoder",
-                Self::FailedToCopyCodecParamsToDecoder => "Failed to copy codec params to decoder",
-                Self::FailedToOpenCodec => "Failed to open codec",
-                Self::FailedToAllocFrame => "Failed to alloc frame",
-                Self::FailedToAllocPacket => "Failed to alloc packet",
-                Self::FailedToSendPacketToDecoder => "Failed to send packet to decoder",
-                Self::FailedToReceiveDecodedFrame => "Failed to receive decoded frame",
+        match self {
+            Self::InvalidUri(uri) => write!(f, "Invalid input {uri:?}: contains a NUL byte"),
+            Self::FailedToOpenInput { uri, error } => {
+                write!(f, "Failed to open input {uri}: {error}")
+            }
+            Self::FailedToFindStreamInfo { uri, error } => {
+                write!(f, "Failed to find stream info of {uri}: {error}")
+            }
+            Self::FailedToFindBestStream(e) => write!(f, "Failed to find best stream: {e}"),
+            Self::NoSuchStream(index) => write!(f, "No stream with index {index}"),
+            Self::FailedToReadFrame(e) => write!(f, "Failed to read frame: {e}"),
+            Self::FailedToFindDecoder(codec_id) => {
+                write!(f, "Failed to find decoder for codec {codec_id}")
+            }
+            Self::FailedToCreateDecoder(codec_id) => {
+                write!(f, "Failed to create decoder for codec {codec_id}")
+            }
+            Self::FailedToCopyCodecParamsToDecoder { codec_id, error } => write!(
+                f,
+                "Failed to copy codec params to decoder for codec {codec_id}: {error}"
+            ),
+            Self::FailedToOpenCodec { codec_id, error } => {
+                write!(f, "Failed to open codec {codec_id}: {error}")
+            }
+            Self::FailedToAllocFrame => write!(f, "Failed to alloc frame"),
+            Self::FailedToAllocPacket => write!(f, "Failed to alloc packet"),
+            Self::FailedToAllocCodecParams => write!(f, "Failed to alloc codec params"),
+            Self::FailedToCopyCodecParams(e) => write!(f, "Failed to copy codec params: {e}"),
+            Self::FailedToReferenceFrame(e) => write!(f, "Failed to reference frame: {e}"),
+            Self::FailedToReferencePacket(e) => write!(f, "Failed to reference packet: {e}"),
+            Self::PacketFromOtherStream { expected, got } => write!(
+                f,
+                "Got a packet from stream {got} for the decoder of stream {expected}"
+            ),
+            Self::FailedToSendPacketToDecoder(e) => {
+                write!(f, "Failed to send packet to decoder: {e}")
+            }
+            Self::FailedToReceiveDecodedFrame(e) => {
+                write!(f, "Failed to receive decoded frame: {e}")
             }
-        )
+            Self::FailedToSeek(e) => write!(f, "Failed to seek: {e}"),
+        }
     }
 }
@@ -18,6 +18,6 @@ pub(crate) mod bindings;
 pub mod core;
 pub mod decoding;
 pub mod demuxing;
+pub mod encoding;
 pub mod error;
 pub mod muxing;
-pub mod encoding;
@@ -1,9 +1,20 @@
 #include <libavformat/avformat.h>
 #include <libavcodec/avcodec.h>
 #include <libavutil/avutil.h>
+#include <libavutil/imgutils.h>
+#include <libavutil/pixdesc.h>
+#include <libavutil/samplefmt.h>
+#include <libavutil/channel_layout.h>
 
 #include <errno.h>
 
 // Get the values from these macros because calling them from rust is not possible
 const int sc_libav_averror_eof = AVERROR_EOF;
 const int sc_libav_averror_eagain = AVERROR(EAGAIN);
+const int sc_libav_averror_einval = AVERROR(EINVAL);
+const int sc_libav_averror_enomem = AVERROR(ENOMEM);
+const int sc_libav_averror_enoent = AVERROR(ENOENT);
+const int sc_libav_averror_eacces = AVERROR(EACCES);
+const int sc_libav_averror_invaliddata = AVERROR_INVALIDDATA;
+const int sc_libav_averror_decoder_not_found = AVERROR_DECODER_NOT_FOUND;
+const int64_t sc_libav_av_nopts_value = AV_NOPTS_VALUE;
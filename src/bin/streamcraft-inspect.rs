@@ -0,0 +1,167 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! List the elements StreamCraft was built with, describe one of them, or list what libav
+//! provides.
+
+use std::process::ExitCode;
+
+use libav::core::{self, MediaType};
+use streamcraft::{
+    element_traits::{PadPresence, PadTemplate},
+    registry::{self, ElementFactory},
+};
+
+const USAGE: &str = "\
+Usage: streamcraft-inspect [OPTIONS] [ELEMENT]
+
+List every element, or describe ELEMENT.
+
+Options:
+    -l, --libav  List the codecs and demuxers libav provides
+    -h, --help   Print this message";
+
+fn format_version(version: u32) -> String {
+    let (major, minor, micro) = core::version_triple(version);
+    format!("{major}.{minor}.{micro}")
+}
+
+fn print_versions() {
+    println!(
+        "StreamCraft {} (libavformat {}, libavcodec {})",
+        env!("CARGO_PKG_VERSION"),
+        format_version(core::version()),
+        format_version(core::codec_version()),
+    );
+}
+
+fn print_elements() {
+    print_versions();
+    println!();
+
+    let factories = registry::factories();
+    for factory in &factories {
+        println!(
+            "{:<16} {:<24} {}",
+            factory.name, factory.classification, factory.description
+        );
+    }
+
+    println!();
+    println!(
+        "{} elements, {} codecs, {} demuxers",
+        factories.len(),
+        core::codecs().len(),
+        core::demuxers().len()
+    );
+}
+
+fn print_pad_templates(direction: &str, templates: &[PadTemplate]) {
+    for template in templates {
+        let presence = match template.presence {
+            PadPresence::Always => "Always",
+            PadPresence::Sometimes => "Sometimes",
+            PadPresence::Request => "Request",
+        };
+        println!("    {direction} {} ({presence})", template.name);
+        println!("        {}", template.caps);
+    }
+}
+
+fn print_element(factory: &ElementFactory) {
+    println!("Name:           {}", factory.name);
+    println!("Classification: {}", factory.classification);
+    println!("Description:    {}", factory.description);
+
+    let architecture = factory.architecture();
+    println!();
+    println!("Pad templates:");
+    if architecture.sinks.is_empty() && architecture.srcs.is_empty() {
+        println!("    none");
+    }
+    print_pad_templates("SINK", &architecture.sinks);
+    print_pad_templates("SRC ", &architecture.srcs);
+
+    let properties = factory.properties();
+    println!();
+    println!("Properties:");
+    if properties.is_empty() {
+        println!("    none");
+    }
+    for spec in properties {
+        let access = match (spec.readable, spec.writable) {
+            (true, true) => format!("readable, writable in {} and lower", spec.mutable_in),
+            (true, false) => String::from("read only"),
+            (false, true) => format!("write only, in {} and lower", spec.mutable_in),
+            (false, false) => String::from("no access"),
+        };
+        println!("    {:<16} {}", spec.name, spec.description);
+        println!("        {}", spec.ty);
+        println!("        Default: {}", spec.default);
+        println!("        Flags: {access}");
+    }
+}
+
+fn print_libav() {
+    print_versions();
+
+    println!();
+    println!("Codecs:");
+    for codec in core::codecs() {
+        println!(
+            "    {}{}{} {:<24} {}",
+            if codec.decoder { 'D' } else { '.' },
+            if codec.encoder { 'E' } else { '.' },
+            match codec.media_type {
+                MediaType::Video => 'V',
+                MediaType::Audio => 'A',
+                MediaType::Subtitle => 'S',
+                MediaType::Data => 'D',
+                MediaType::Attachment => 'T',
+                MediaType::Other => '.',
+            },
+            codec.name,
+            codec.long_name
+        );
+    }
+
+    println!();
+    println!("Demuxers:");
+    for demuxer in core::demuxers() {
+        println!("    {:<24} {}", demuxer.name, demuxer.long_name);
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        [] => print_elements(),
+        ["-h" | "--help"] => println!("{USAGE}"),
+        ["-l" | "--libav"] => print_libav(),
+        [name] if !name.starts_with('-') => match registry::find(name) {
+            Some(factory) => print_element(&factory),
+            None => {
+                eprintln!("No such element: {name}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
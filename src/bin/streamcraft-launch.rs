@@ -0,0 +1,254 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Build a pipeline from a text description and run it until EOS or Ctrl-C.
+
+use std::{
+    collections::BTreeMap,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use streamcraft::pipeline::{
+    bus::BusMessage, graph::NodeId, parse::parse_launch, state::State, stats::LinkStats, Pipeline,
+};
+
+const USAGE: &str = "\
+Usage: streamcraft-launch [OPTIONS] DESCRIPTION...
+
+Run the pipeline described by DESCRIPTION until EOS or Ctrl-C, e.g.
+
+    streamcraft-launch filesrc location=README.md ! bytes2text ! stdoutlog
+
+The description can also be given as one argument, which is parsed as it is.
+
+Options:
+    -v, --verbose  Print the negotiated caps of every link
+    -s, --stats    Print how much data every element received and sent on exit
+    -h, --help     Print this message";
+
+/// Exit codes besides success.
+const EXIT_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_INTERRUPTED: u8 = 130;
+
+struct Options {
+    verbose: bool,
+    stats: bool,
+    description: String,
+}
+
+/// Quote an argument that the shell has already unquoted so that the parser sees one word, e.g.
+/// `location=my file.txt` becomes `location="my file.txt"`. Only used when the description is
+/// split over several arguments.
+fn quote_arg(arg: &str) -> String {
+    if !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        return arg.to_string();
+    }
+
+    let (property, value) = match arg.split_once('=') {
+        Some((property, value)) => (format!("{property}="), value),
+        None => (String::new(), arg),
+    };
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{property}\"{escaped}\"")
+}
+
+/// Returns `None` if help was asked for.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        verbose: false,
+        stats: false,
+        description: String::new(),
+    };
+    let mut words = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" if words.is_empty() => options.verbose = true,
+            "-s" | "--stats" if words.is_empty() => options.stats = true,
+            "-h" | "--help" if words.is_empty() => return Ok(None),
+            option if words.is_empty() && option.starts_with('-') => {
+                return Err(format!("Unknown option {option}"))
+            }
+            word => words.push(word.to_string()),
+        }
+    }
+
+    if words.is_empty() {
+        return Err(String::from("No pipeline description"));
+    }
+    options.description = match words.as_slice() {
+        [description] => description.clone(),
+        words => words
+            .iter()
+            .map(|word| quote_arg(word))
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+
+    Ok(Some(options))
+}
+
+fn print_message(msg: &BusMessage) {
+    match msg {
+        BusMessage::Error { source, error } => eprintln!("ERROR from {source}: {error}"),
+        BusMessage::Warning { source, message } => eprintln!("WARNING from {source}: {message}"),
+        BusMessage::Info { source, message } => println!("INFO from {source}: {message}"),
+        BusMessage::Eos => println!("Got EOS"),
+        BusMessage::StateChanged { source, old, new } => {
+            println!("{source}: state changed from {old} to {new}")
+        }
+        BusMessage::Progress { source, percent } => println!("{source}: {percent}%"),
+        BusMessage::Application { source, structure } => println!("{source}: {structure}"),
+        BusMessage::ElementFinished(_) => {}
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn print_stats(pipeline: &Pipeline, elapsed: Duration) {
+    // What every element received and sent
+    let mut elements = BTreeMap::<NodeId, (LinkStats, LinkStats)>::new();
+    for (edge, stats) in pipeline.link_stats() {
+        let sent = &mut elements.entry(edge.src.0).or_default().1;
+        sent.buffers += stats.buffers;
+        sent.bytes += stats.bytes;
+        let received = &mut elements.entry(edge.sink.0).or_default().0;
+        received.buffers += stats.buffers;
+        received.bytes += stats.bytes;
+    }
+
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    println!("Statistics after {:.3} s:", elapsed.as_secs_f64());
+    for (node, (received, sent)) in elements {
+        println!(
+            "    {:<20} in: {:>8} buffers {:>12} ({}/s)  out: {:>8} buffers {:>12} ({}/s)",
+            pipeline.element_path(node).unwrap_or("?"),
+            received.buffers,
+            format_bytes(received.bytes as f64),
+            format_bytes(received.bytes as f64 / seconds),
+            sent.buffers,
+            format_bytes(sent.bytes as f64),
+            format_bytes(sent.bytes as f64 / seconds),
+        );
+    }
+}
+
+fn run(options: &Options) -> u8 {
+    let mut pipeline = match parse_launch(&options.description) {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            eprintln!("ERROR: Invalid pipeline description: {e}");
+            eprintln!("{}", e.highlight(&options.description));
+            return EXIT_ERROR;
+        }
+    };
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupted_clone = Arc::clone(&interrupted);
+    if let Err(e) = ctrlc::set_handler(move || interrupted_clone.store(true, Ordering::SeqCst)) {
+        eprintln!("WARNING: Failed to handle Ctrl-C: {e}");
+    }
+
+    let bus = pipeline.bus();
+    let start = Instant::now();
+    if let Err(e) = pipeline.play() {
+        bus.try_iter().for_each(|msg| print_message(&msg));
+        eprintln!("ERROR: Failed to start the pipeline: {e}");
+        return EXIT_ERROR;
+    }
+
+    if options.verbose {
+        for (edge, caps) in pipeline.negotiated_caps() {
+            println!(
+                "{}:{} -> {}:{}: {caps}",
+                pipeline.element_path(edge.src.0).unwrap_or("?"),
+                edge.src.1,
+                pipeline.element_path(edge.sink.0).unwrap_or("?"),
+                edge.sink.1,
+            );
+        }
+    }
+
+    let status = loop {
+        if interrupted.load(Ordering::SeqCst) {
+            println!("Interrupted, stopping the pipeline");
+            break EXIT_INTERRUPTED;
+        }
+
+        match bus.recv_timeout(Duration::from_millis(100)) {
+            Ok(Some(msg)) => {
+                print_message(&msg);
+                match msg {
+                    BusMessage::Error { .. } => break EXIT_ERROR,
+                    BusMessage::Eos => break 0,
+                    _ => {}
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                break EXIT_ERROR;
+            }
+        }
+    };
+    let elapsed = start.elapsed();
+
+    let status = match pipeline.set_state(State::Null) {
+        Ok(()) => status,
+        Err(e) => {
+            eprintln!("ERROR: Failed to stop the pipeline: {e}");
+            EXIT_ERROR
+        }
+    };
+    // Errors that were posted while stopping
+    bus.try_iter().for_each(|msg| print_message(&msg));
+
+    if options.stats {
+        print_stats(&pipeline, elapsed);
+    }
+
+    status
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    ExitCode::from(run(&options))
+}
@@ -0,0 +1,472 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Capabilities describe the formats a pad can handle.
+//!
+//! A [`Caps`] is a list of [`Structure`]s in order of preference. Each structure has a media
+//! type like [`TEXT`] and named fields that narrow the format down, e.g. the sample rate of
+//! raw audio. Fields can hold ranges and lists of values until the caps are fixated.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// Text, the field `format` is the encoding.
+pub const TEXT: &str = "text/x-raw";
+/// Arbitrary bytes.
+pub const BYTES: &str = "application/octet-stream";
+/// Compressed packets from libav, the field `media-type` is `video` or `audio`, `codec-id`
+/// is the libav codec id and `time-base` is the time base of the timestamps. The parameters
+/// of the stream a decoder needs are in `stream-index`, `codec-data`, `format`, `width`,
+/// `height`, `sample-rate`, `channels`, `bit-rate`, `block-align` and `bits-per-coded-sample`.
+pub const AV_PACKET: &str = "application/x-av-packet";
+/// Decoded video, the field `format` is the libav pixel format, e.g. `yuv420p`, and `width`
+/// and `height` are the size in pixels.
+pub const VIDEO_RAW: &str = "video/x-raw";
+/// Decoded audio, the field `format` is the libav sample format, e.g. `s16`, `rate` the
+/// sample rate, `channels` the number of channels, `channel-layout` the libav channel layout,
+/// e.g. `stereo`, and `layout` is `interleaved` or `planar`.
+pub const AUDIO_RAW: &str = "audio/x-raw";
+
+/// Caps for UTF-8 [`TEXT`].
+pub fn text_utf8() -> Caps {
+    Caps::new(Structure::new(TEXT).field("format", "utf8"))
+}
+
+/// Caps for [`BYTES`].
+pub fn bytes() -> Caps {
+    Caps::new_simple(BYTES)
+}
+
+/// Caps for [`VIDEO_RAW`] video of any format.
+pub fn video_raw() -> Caps {
+    Caps::new_simple(VIDEO_RAW)
+}
+
+/// Caps for [`AUDIO_RAW`] audio of any format.
+pub fn audio_raw() -> Caps {
+    Caps::new_simple(AUDIO_RAW)
+}
+
+/// Caps for [`AV_PACKET`]s with the media type `media_type`.
+pub fn av_packet(media_type: &str) -> Caps {
+    Caps::new(Structure::new(AV_PACKET).field("media-type", media_type))
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Value {
+    Int(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// Numerator and denominator.
+    Fraction(i64, i64),
+    /// Inclusive range.
+    IntRange(i64, i64),
+    /// Any one of the values.
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn is_fixed(&self) -> bool {
+        matches!(
+            self,
+            Value::Int(_) | Value::Str(_) | Value::Bytes(_) | Value::Fraction(..)
+        )
+    }
+
+    /// Get the values that are in both `self` and `other`.
+    pub fn intersect(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::List(values), other) | (other, Value::List(values)) => {
+                let mut common = values
+                    .iter()
+                    .filter_map(|value| value.intersect(other))
+                    .collect::<Vec<Value>>();
+                match common.len() {
+                    0 => None,
+                    1 => common.pop(),
+                    _ => Some(Value::List(common)),
+                }
+            }
+            (Value::Int(a), Value::Int(b)) => (a == b).then_some(Value::Int(*a)),
+            (Value::Str(a), Value::Str(b)) => (a == b).then(|| Value::Str(a.clone())),
+            (Value::Bytes(a), Value::Bytes(b)) => (a == b).then(|| Value::Bytes(a.clone())),
+            (Value::Fraction(a_num, a_den), Value::Fraction(b_num, b_den)) => {
+                (a_num * b_den == b_num * a_den).then_some(Value::Fraction(*a_num, *a_den))
+            }
+            (Value::Int(n), Value::IntRange(min, max))
+            | (Value::IntRange(min, max), Value::Int(n)) => {
+                (min <= n && n <= max).then_some(Value::Int(*n))
+            }
+            (Value::IntRange(a_min, a_max), Value::IntRange(b_min, b_max)) => {
+                let (min, max) = (*a_min.max(b_min), *a_max.min(b_max));
+                match min.cmp(&max) {
+                    std::cmp::Ordering::Less => Some(Value::IntRange(min, max)),
+                    std::cmp::Ordering::Equal => Some(Value::Int(min)),
+                    std::cmp::Ordering::Greater => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Pick a single value. Ranges fixate to their minimum and lists to their first value.
+    pub fn fixate(&self) -> Value {
+        match self {
+            Value::IntRange(min, _) => Value::Int(*min),
+            Value::List(values) => values.first().map(Value::fixate).unwrap_or(self.clone()),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Value::Bytes(value.to_vec())
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Bytes(bytes) => bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}")),
+            Value::Fraction(num, den) => write!(f, "{num}/{den}"),
+            Value::IntRange(min, max) => write!(f, "[{min}, {max}]"),
+            Value::List(values) => {
+                write!(f, "{{ ")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// A media type with fields. Fields that are not present are unconstrained.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Structure {
+    pub name: String,
+    fields: BTreeMap<String, Value>,
+}
+
+impl Structure {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Builder style [`Structure::set`].
+    pub fn field(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        self.fields.insert(name.to_string(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(Value::Int(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn get_fraction(&self, name: &str) -> Option<(i64, i64)> {
+        match self.get(name) {
+            Some(Value::Fraction(num, den)) => Some((*num, *den)),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(Value::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        match self.get(name) {
+            Some(Value::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.fields.values().all(Value::is_fixed)
+    }
+
+    pub fn intersect(&self, other: &Structure) -> Option<Structure> {
+        if self.name != other.name {
+            return None;
+        }
+
+        let mut fields = self.fields.clone();
+        for (name, value) in &other.fields {
+            let value = match fields.get(name) {
+                Some(own) => own.intersect(value)?,
+                None => value.clone(),
+            };
+            fields.insert(name.clone(), value);
+        }
+
+        Some(Self {
+            name: self.name.clone(),
+            fields,
+        })
+    }
+
+    pub fn fixate(&self) -> Structure {
+        Self {
+            name: self.name.clone(),
+            fields: self
+                .fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.fixate()))
+                .collect(),
+        }
+    }
+}
+
+impl Display for Structure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (name, value) in &self.fields {
+            write!(f, ", {name}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A list of structures in order of preference, or any format at all.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Caps {
+    any: bool,
+    structures: Vec<Structure>,
+}
+
+impl Caps {
+    pub fn new(structure: Structure) -> Self {
+        Self::from_structures(vec![structure])
+    }
+
+    pub fn from_structures(structures: Vec<Structure>) -> Self {
+        Self {
+            any: false,
+            structures,
+        }
+    }
+
+    /// Caps that are compatible with everything.
+    pub fn any() -> Self {
+        Self {
+            any: true,
+            structures: Vec::new(),
+        }
+    }
+
+    /// Caps that are compatible with nothing.
+    pub fn empty() -> Self {
+        Self::from_structures(Vec::new())
+    }
+
+    /// Caps with a single structure without fields.
+    pub fn new_simple(name: &str) -> Self {
+        Self::new(Structure::new(name))
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.any
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.any && self.structures.is_empty()
+    }
+
+    /// Whether the caps describe exactly one format.
+    pub fn is_fixed(&self) -> bool {
+        !self.any && self.structures.len() == 1 && self.structures[0].is_fixed()
+    }
+
+    pub fn structures(&self) -> &[Structure] {
+        &self.structures
+    }
+
+    /// Get the first structure, which is the only one if the caps are fixed.
+    pub fn structure(&self) -> Option<&Structure> {
+        self.structures.first()
+    }
+
+    /// Get the formats that are in both `self` and `other`, in the order of preference of `self`.
+    pub fn intersect(&self, other: &Caps) -> Caps {
+        if self.any {
+            return other.clone();
+        }
+        if other.any {
+            return self.clone();
+        }
+
+        Caps::from_structures(
+            self.structures
+                .iter()
+                .flat_map(|own| {
+                    other
+                        .structures
+                        .iter()
+                        .filter_map(move |other| own.intersect(other))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn can_intersect(&self, other: &Caps) -> bool {
+        !self.intersect(other).is_empty()
+    }
+
+    /// Reduce the caps to the single most preferred format.
+    pub fn fixate(&self) -> Caps {
+        match self.structures.first() {
+            Some(structure) if !self.any => Caps::new(structure.fixate()),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Display for Caps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.any {
+            return write!(f, "ANY");
+        }
+        if self.structures.is_empty() {
+            return write!(f, "EMPTY");
+        }
+
+        for (i, structure) in self.structures.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{structure}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_intersect() {
+        assert_eq!(
+            Value::IntRange(1, 10).intersect(&Value::IntRange(5, 20)),
+            Some(Value::IntRange(5, 10))
+        );
+        assert_eq!(
+            Value::IntRange(1, 10).intersect(&Value::IntRange(10, 20)),
+            Some(Value::Int(10))
+        );
+        assert_eq!(Value::IntRange(1, 10).intersect(&Value::Int(11)), None);
+        assert_eq!(
+            Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)])
+                .intersect(&Value::IntRange(2, 5)),
+            Some(Value::List(vec![Value::Int(2), Value::Int(3)]))
+        );
+        assert_eq!(
+            Value::from("utf8").intersect(&Value::List(vec!["ascii".into(), "utf8".into()])),
+            Some(Value::from("utf8"))
+        );
+        assert_eq!(Value::from("utf8").intersect(&Value::Int(8)), None);
+    }
+
+    #[test]
+    fn test_caps_intersect_and_fixate() {
+        let src = Caps::from_structures(vec![
+            Structure::new("audio/x-raw")
+                .field("rate", Value::List(vec![48000.into(), 44100.into()]))
+                .field("channels", Value::IntRange(1, 8)),
+            Structure::new(TEXT).field("format", "utf8"),
+        ]);
+        let sink = Caps::new(
+            Structure::new("audio/x-raw")
+                .field("rate", Value::IntRange(8000, 44100))
+                .field("format", "s16"),
+        );
+
+        let common = src.intersect(&sink);
+        assert_eq!(
+            common,
+            Caps::new(
+                Structure::new("audio/x-raw")
+                    .field("rate", 44100)
+                    .field("channels", Value::IntRange(1, 8))
+                    .field("format", "s16")
+            )
+        );
+        assert!(!common.is_fixed());
+
+        let fixed = common.fixate();
+        assert!(fixed.is_fixed());
+        assert_eq!(fixed.structure().unwrap().get_int("channels"), Some(1));
+
+        assert!(src.intersect(&Caps::new_simple(BYTES)).is_empty());
+        assert_eq!(Caps::any().intersect(&sink), sink);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Run loops for the common kinds of elements.
+//!
+//! An element with a single `src` pad, a single `sink` pad, or one of each implements
+//! [`Source`], [`Sink`] or [`Transform`] and calls [`run_source`], [`run_sink`] or
+//! [`run_transform`] from [`Element::run`]. Messages, state changes, queries and events that
+//! need no special handling are taken care of.
+//!
+//! # Example
+//! ```
+//!use streamcraft::{
+//!    caps, element_def,
+//!    element_traits::{
+//!        base::{self, Transform},
+//!        Element, ElementArchitecture, PadTemplate,
+//!    },
+//!    pipeline::{error::Error, Data, ElementContext},
+//!};
+//!
+//!#[derive(Default)]
+//!struct Uppercase {}
+//!
+//!impl Transform for Uppercase {
+//!    fn transform(&mut self, data: Data) -> Result<Vec<Data>, Error> {
+//!        match data {
+//!            Data::Text(text) => Ok(vec![Data::Text(text.to_uppercase())]),
+//!            _ => Err(Error::ReceivedInvalidDatagramFromParent),
+//!        }
+//!    }
+//!}
+//!
+//!impl Element for Uppercase {
+//!    fn get_architecture(&self) -> ElementArchitecture {
+//!        ElementArchitecture {
+//!            sinks: vec![PadTemplate::always("sink", caps::text_utf8())],
+//!            srcs: vec![PadTemplate::always("src", caps::text_utf8())],
+//!        }
+//!    }
+//!
+//!    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+//!        base::run_transform(self, ctx)
+//!    }
+//!}
+//!
+//!element_def! {
+//!    Uppercase,
+//!    "uppercase"
+//!}
+//! ```
+
+use crate::pipeline::{
+    error::Error,
+    event::{Event, Segment},
+    Data, Datagram, ElementContext, Input, Message,
+};
+
+use super::Element;
+
+/// An element that produces data on its `src` pad.
+pub trait Source: Element {
+    /// Produce the next data, or `None` when there is no more.
+    fn create(&mut self) -> Result<Option<Data>, Error>;
+}
+
+/// An element that consumes data from its `sink` pad.
+pub trait Sink: Element {
+    fn render(&mut self, data: Data) -> Result<(), Error>;
+    /// Called for every event that arrives on the `sink` pad.
+    fn sink_event(&mut self, _event: &Event) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// An element that turns the data on its `sink` pad into data on its `src` pad.
+pub trait Transform: Element {
+    /// Turn `data` into any number of outputs.
+    fn transform(&mut self, data: Data) -> Result<Vec<Data>, Error>;
+    /// Called for every event that arrives on the `sink` pad before it is forwarded. The
+    /// returned data is pushed before the event, e.g. what is left when the stream ends.
+    fn sink_event(&mut self, _event: &Event) -> Result<Vec<Data>, Error> {
+        Ok(Vec::new())
+    }
+}
+
+/// Start the stream and push what `element` creates, once per iteration, until it is done.
+pub fn run_source<S: Source>(element: &mut S, ctx: &mut ElementContext) -> Result<(), Error> {
+    ctx.push_event(Event::StreamStart)?;
+    ctx.push_event(Event::Segment(Segment::default()))?;
+
+    loop {
+        match ctx.recv(element)? {
+            Input::Control(Message::Iter) => match element.create()? {
+                Some(data) => {
+                    ctx.push("src", Datagram::Data(data))?;
+                    ctx.iter_fin()?;
+                }
+                None => {
+                    ctx.push_event(Event::Eos)?;
+                    break;
+                }
+            },
+            Input::Control(Message::Quit) => break,
+            _ => return Err(Error::ReceivedInvalidDatagramFromParent),
+        }
+    }
+
+    Ok(())
+}
+
+/// Give `element` everything that arrives on the `sink` pad until upstream quits.
+pub fn run_sink<S: Sink>(element: &mut S, ctx: &mut ElementContext) -> Result<(), Error> {
+    loop {
+        match ctx.recv(element)? {
+            Input::Sink(_, Datagram::Message(Message::Quit)) => break,
+            Input::Sink(_, Datagram::Data(data)) => element.render(data)?,
+            Input::Sink(_, Datagram::Event(event)) => element.sink_event(&event)?,
+            _ => return Err(Error::ReceivedInvalidDatagramFromParent),
+        }
+    }
+
+    Ok(())
+}
+
+/// Transform everything that arrives on the `sink` pad and push it on the `src` pad until
+/// upstream quits. Events are forwarded.
+pub fn run_transform<T: Transform>(element: &mut T, ctx: &mut ElementContext) -> Result<(), Error> {
+    loop {
+        match ctx.recv(element)? {
+            Input::Sink(_, Datagram::Message(Message::Quit)) => break,
+            Input::Sink(_, Datagram::Data(data)) => {
+                for data in element.transform(data)? {
+                    ctx.push("src", Datagram::Data(data))?;
+                }
+            }
+            Input::Sink(_, Datagram::Event(event)) => {
+                for data in element.sink_event(&event)? {
+                    ctx.push("src", Datagram::Data(data))?;
+                }
+                ctx.push_event(event)?;
+            }
+            _ => return Err(Error::ReceivedInvalidDatagramFromParent),
+        }
+    }
+
+    Ok(())
+}
@@ -13,86 +13,220 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
-use crossbeam_channel::Receiver;
+pub mod base;
+pub mod property;
 
-use crate::pipeline::{self, Datagram, Parent};
+use std::cmp::Ordering;
 
-#[derive(PartialEq, Clone, Debug)]
-pub enum CommonFormat {
-    Text,
-    Bytes,
-    AVPacket, // TODO: Only include when `av` feature is enabled
+use crate::{
+    caps::Caps,
+    pipeline::{self, event::Event, query::Query, state::StateChange, ElementContext},
+};
+
+use property::{PropertySpec, PropertyValue};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PadDirection {
+    Src,
+    Sink,
 }
 
+/// When pads created from a [`PadTemplate`] exist.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PadPresence {
+    /// The pad always exists.
+    Always,
+    /// The pad exists depending on the element, e.g. a demuxer only has an audio pad if the
+    /// input has an audio stream. See [`Element::has_pad`].
+    Sometimes,
+    /// A new pad is created every time the template is linked.
+    Request,
+}
+
+/// Describes the pads an element can have.
+///
+/// Names of templates that can create more than one pad contain `%u`, which is replaced with
+/// a number, e.g. the template `src_%u` creates the pads `src_0`, `src_1`, ...
 #[derive(PartialEq, Clone, Debug)]
-pub enum Sink {
-    One(CommonFormat),
-    None,
+pub struct PadTemplate {
+    pub name: String,
+    pub presence: PadPresence,
+    pub caps: Caps,
 }
 
-impl Sink {
-    pub fn has_none(&self) -> bool {
-        matches!(self, Sink::None)
+impl PadTemplate {
+    pub fn new(name: &str, presence: PadPresence, caps: Caps) -> Self {
+        Self {
+            name: name.to_string(),
+            presence,
+            caps,
+        }
+    }
+
+    /// Template for a pad that always exists.
+    pub fn always(name: &str, caps: Caps) -> Self {
+        Self::new(name, PadPresence::Always, caps)
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        self.name.contains("%u")
+    }
+
+    /// Check if a pad named `name` can be created from this template.
+    pub fn matches(&self, name: &str) -> bool {
+        match self.name.split_once("%u") {
+            Some((prefix, suffix)) => name
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(suffix))
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())),
+            None => self.name == name,
+        }
+    }
+
+    /// Name of the pad with number `n` created from this template.
+    pub fn pad_name(&self, n: usize) -> String {
+        self.name.replacen("%u", &n.to_string(), 1)
+    }
+
+    /// Check if this template has at least one format in common with `other`.
+    pub fn is_compatible_with(&self, other: &PadTemplate) -> bool {
+        self.caps.can_intersect(&other.caps)
     }
 }
 
-#[derive(PartialEq, Clone, Debug)]
-pub enum Srcs {
-    One(CommonFormat),
-    Two((CommonFormat, CommonFormat)),
-    None,
+#[derive(Clone, Debug)]
+pub struct ElementArchitecture {
+    pub sinks: Vec<PadTemplate>,
+    pub srcs: Vec<PadTemplate>,
 }
 
-impl Srcs {
-    pub fn has_none(&self) -> bool {
-        matches!(self, Srcs::None)
+impl ElementArchitecture {
+    pub fn is_src(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Find the sink pad template a pad named `name` belongs to.
+    pub fn sink_template(&self, name: &str) -> Option<&PadTemplate> {
+        find_template(&self.sinks, name)
+    }
+
+    /// Find the src pad template a pad named `name` belongs to.
+    pub fn src_template(&self, name: &str) -> Option<&PadTemplate> {
+        find_template(&self.srcs, name)
+    }
+
+    pub fn template(&self, direction: PadDirection, name: &str) -> Option<&PadTemplate> {
+        match direction {
+            PadDirection::Src => self.src_template(name),
+            PadDirection::Sink => self.sink_template(name),
+        }
     }
 }
 
-pub fn sink_is_compatible_with_src(sink: Sink, src: Srcs) -> bool {
-    match sink {
-        Sink::One(format) => src == Srcs::One(format),
-        _ => false,
+/// Order pad names by their number instead of alphabetically, e.g. `sink_2` before `sink_10`.
+pub fn compare_pad_names(a: &str, b: &str) -> Ordering {
+    fn split(name: &str) -> (&str, Option<u64>) {
+        let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        (prefix, name[prefix.len()..].parse().ok())
     }
+
+    split(a).cmp(&split(b))
 }
 
-#[derive(Clone, Debug)]
-pub struct ElementArchitecture {
-    pub sink: Sink,
-    pub srcs: Srcs,
+fn find_template<'a>(templates: &'a [PadTemplate], name: &str) -> Option<&'a PadTemplate> {
+    templates
+        .iter()
+        .find(|template| template.name == name || template.matches(name))
 }
 
-#[derive(PartialEq, Clone, Debug)]
-pub enum ElementType {
-    TextSink,
-    TextSrc,
-    BytesSrc,
-    BytesSink,
-    AVPacketSrc,       // TODO: Only include when `av` feature is enabled
-    AVPacketVideoSink, // TODO: Only include when `av` feature is enabled
-    AVPacketAudioSink, // TODO: Only include when `av` feature is enabled
+/// Implemented by [`element_def!`](crate::element_def).
+pub trait ElementName {
+    /// The name of the kind of element, e.g. `filesrc`.
+    fn name(&self) -> &'static str;
 }
 
-pub trait Element: Sync + Send {
-    fn get_sink_type(&self) -> ElementType;
+pub trait Element: ElementName + Sync + Send {
     fn get_architecture(&self) -> ElementArchitecture;
-    fn run(
+    /// Check if the [`PadPresence::Sometimes`] pad named `name` currently exists.
+    fn has_pad(&self, _name: &str) -> bool {
+        false
+    }
+    /// Get the formats the pad named `pad` can handle right now. Called during negotiation, after
+    /// the caps of all sink pads have been set. Defaults to the caps of the pad template.
+    fn query_caps(&self, direction: PadDirection, pad: &str) -> Caps {
+        self.get_architecture()
+            .template(direction, pad)
+            .map(|template| template.caps.clone())
+            .unwrap_or(Caps::empty())
+    }
+    /// Called with the fixed caps that were negotiated for the pad named `pad`.
+    fn set_caps(
+        &mut self,
+        _direction: PadDirection,
+        _pad: &str,
+        _caps: &Caps,
+    ) -> Result<(), pipeline::error::Error> {
+        Ok(())
+    }
+    /// Answer `query`, which arrived on the pad named `pad`, and return whether it was answered.
+    /// `pad` is empty for queries on the element itself. Called from the element's thread.
+    /// Caps queries that are not answered here are answered with [`Element::query_caps`].
+    fn query(&mut self, _direction: PadDirection, _pad: &str, _query: &mut Query) -> bool {
+        false
+    }
+    /// Name of the pad on the other side of the element to pass on `query`, which arrived on the
+    /// pad named `pad` and was not answered, e.g. the active input of a selector. Queries are
+    /// answered by a single branch, by default the linked pad that comes first by name.
+    fn query_forward_pad(&self, _direction: PadDirection, _pad: &str) -> Option<String> {
+        None
+    }
+    /// Handle `event`, which travels upstream and arrived on the src pad named `pad`, and return
+    /// whether it was handled. `pad` is empty for events sent to the element itself. Events that
+    /// are not handled are forwarded through every linked sink pad.
+    fn upstream_event(
         &mut self,
-        parent_datagram_receiver: Receiver<Datagram>,
-    ) -> Result<(), pipeline::error::Error>;
-    fn set_parent(&mut self, parent: Parent);
-    fn cleanup(&mut self) -> Result<(), pipeline::error::Error>;
+        _ctx: &mut ElementContext,
+        _pad: &str,
+        _event: &Event,
+    ) -> Result<bool, pipeline::error::Error> {
+        Ok(false)
+    }
+    /// Called for every state change of the pipeline. Changes to and from
+    /// [`State::Playing`](crate::pipeline::state::State::Playing) happen in the element's thread,
+    /// the others happen before the thread is spawned or after it has been joined.
+    fn change_state(&mut self, _change: StateChange) -> Result<(), pipeline::error::Error> {
+        Ok(())
+    }
+    /// Describe the properties of the element.
+    fn properties(&self) -> Vec<PropertySpec> {
+        Vec::new()
+    }
+    /// Get the value of the property named `name`.
+    fn property(&self, _name: &str) -> Option<PropertyValue> {
+        None
+    }
+    /// Set the property named `name`. `value` has already been checked against the
+    /// [`PropertySpec`] of the property. Called from the element's thread while it is running.
+    fn set_property(
+        &mut self,
+        name: &str,
+        _value: PropertyValue,
+    ) -> Result<(), pipeline::error::Error> {
+        Err(pipeline::error::Error::NoSuchProperty(name.to_string()))
+    }
+    /// Run the element until it is done or told to quit. Spawned in its own thread by the
+    /// pipeline with the pads wired up in `ctx`.
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), pipeline::error::Error>;
 }
 
-// TODO: Make it so elements are NOT valid when this macro is not used.
+/// Give an element its name. With a classification and a description the element also gets a
+/// `factory()` that creates it with [`Default`], see [`ElementRegistry`](crate::registry::ElementRegistry).
 #[macro_export]
 macro_rules! element_def {
     ($element:ty, $name:literal) => {
-        impl Drop for $element {
-            fn drop(&mut self) {
-                if let Err(e) = self.cleanup() {
-                    $crate::error!("Failed to drop: {e}");
-                }
+        impl $crate::element_traits::ElementName for $element {
+            fn name(&self) -> &'static str {
+                $name
             }
         }
 
@@ -100,4 +234,19 @@ macro_rules! element_def {
             $name
         }
     };
+    ($element:ty, $name:literal, $classification:literal, $description:literal) => {
+        $crate::element_def! {
+            $element,
+            $name
+        }
+
+        impl $element {
+            /// Get the factory of the element.
+            pub fn factory() -> $crate::registry::ElementFactory {
+                $crate::registry::ElementFactory::new($name, $classification, $description, || {
+                    Box::new(<$element>::default())
+                })
+            }
+        }
+    };
 }
@@ -0,0 +1,337 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Named and typed settings of an element that can be listed, read and written by name.
+//!
+//! An element lists its properties with [`Element::properties`]. Values are checked against
+//! the [`PropertySpec`] before [`Element::set_property`] is called, so elements only have to
+//! convert the value.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::pipeline::{error::Error, state::State};
+
+use super::Element;
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    Str(String),
+    /// One of the values of a [`PropertyType::Enum`].
+    Enum(String),
+}
+
+impl PropertyValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            Self::Double(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Get the string of a [`PropertyValue::Str`] or [`PropertyValue::Enum`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) | Self::Enum(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for PropertyValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<u32> for PropertyValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl Display for PropertyValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Int(n) => write!(f, "{n}"),
+            Self::Double(n) => write!(f, "{n}"),
+            Self::Str(s) | Self::Enum(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// The type of a property and the values it can have.
+#[derive(PartialEq, Clone, Debug)]
+pub enum PropertyType {
+    Bool,
+    /// Inclusive range.
+    Int(i64, i64),
+    /// Inclusive range.
+    Double(f64, f64),
+    Str,
+    /// One of the values.
+    Enum(Vec<String>),
+}
+
+impl Display for PropertyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool => write!(f, "Boolean"),
+            Self::Int(min, max) => write!(f, "Integer. Range: {min} - {max}"),
+            Self::Double(min, max) => write!(f, "Double. Range: {min} - {max}"),
+            Self::Str => write!(f, "String"),
+            Self::Enum(values) => write!(f, "Enum. One of: {}", values.join(", ")),
+        }
+    }
+}
+
+/// Describes a property of an element.
+#[derive(PartialEq, Clone, Debug)]
+pub struct PropertySpec {
+    pub name: String,
+    pub description: String,
+    pub ty: PropertyType,
+    pub default: PropertyValue,
+    pub readable: bool,
+    pub writable: bool,
+    /// The highest state the element can be in when the property is written.
+    pub mutable_in: State,
+}
+
+impl PropertySpec {
+    /// A readable and writable property that can only be written before the pipeline is
+    /// started, i.e. in [`State::Ready`] or lower.
+    pub fn new(name: &str, description: &str, ty: PropertyType, default: PropertyValue) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            ty,
+            default,
+            readable: true,
+            writable: true,
+            mutable_in: State::Ready,
+        }
+    }
+
+    pub fn bool(name: &str, description: &str, default: bool) -> Self {
+        Self::new(name, description, PropertyType::Bool, default.into())
+    }
+
+    pub fn int(name: &str, description: &str, min: i64, max: i64, default: i64) -> Self {
+        Self::new(
+            name,
+            description,
+            PropertyType::Int(min, max),
+            default.into(),
+        )
+    }
+
+    pub fn double(name: &str, description: &str, min: f64, max: f64, default: f64) -> Self {
+        Self::new(
+            name,
+            description,
+            PropertyType::Double(min, max),
+            default.into(),
+        )
+    }
+
+    pub fn string(name: &str, description: &str, default: &str) -> Self {
+        Self::new(name, description, PropertyType::Str, default.into())
+    }
+
+    pub fn enumeration(name: &str, description: &str, values: &[&str], default: &str) -> Self {
+        Self::new(
+            name,
+            description,
+            PropertyType::Enum(values.iter().map(|value| value.to_string()).collect()),
+            PropertyValue::Enum(default.to_string()),
+        )
+    }
+
+    /// Builder style, the property can not be written.
+    pub fn read_only(mut self) -> Self {
+        self.writable = false;
+        self
+    }
+
+    /// Builder style, the property can be written while the element is in `state` or lower.
+    pub fn mutable_in(mut self, state: State) -> Self {
+        self.mutable_in = state;
+        self
+    }
+
+    /// Check that `value` fits the property and convert it to the type of the property where
+    /// that is lossless, e.g. a [`PropertyValue::Str`] to a [`PropertyValue::Enum`].
+    pub fn check(&self, value: PropertyValue) -> Result<PropertyValue, Error> {
+        let value = match (&self.ty, value) {
+            (PropertyType::Bool, value @ PropertyValue::Bool(_)) => value,
+            (PropertyType::Int(min, max), PropertyValue::Int(n)) if *min <= n && n <= *max => {
+                PropertyValue::Int(n)
+            }
+            (PropertyType::Double(..), PropertyValue::Int(n)) => {
+                return self.check(PropertyValue::Double(n as f64))
+            }
+            (PropertyType::Double(min, max), PropertyValue::Double(n))
+                if *min <= n && n <= *max =>
+            {
+                PropertyValue::Double(n)
+            }
+            (PropertyType::Str, value @ PropertyValue::Str(_)) => value,
+            (PropertyType::Enum(values), PropertyValue::Str(s) | PropertyValue::Enum(s))
+                if values.contains(&s) =>
+            {
+                PropertyValue::Enum(s)
+            }
+            (_, value) => {
+                return Err(Error::InvalidPropertyValue(format!(
+                    "{} = {value}",
+                    self.name
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Parse `s` as a value of the property, e.g. from a text pipeline description.
+    pub fn parse(&self, s: &str) -> Result<PropertyValue, Error> {
+        let invalid = || Error::InvalidPropertyValue(format!("{} = {s}", self.name));
+        let value = match self.ty {
+            PropertyType::Bool => match s {
+                "true" | "yes" | "1" => PropertyValue::Bool(true),
+                "false" | "no" | "0" => PropertyValue::Bool(false),
+                _ => return Err(invalid()),
+            },
+            PropertyType::Int(..) => PropertyValue::Int(s.parse().map_err(|_| invalid())?),
+            PropertyType::Double(..) => PropertyValue::Double(s.parse().map_err(|_| invalid())?),
+            PropertyType::Str | PropertyType::Enum(_) => PropertyValue::Str(s.to_string()),
+        };
+
+        self.check(value)
+    }
+}
+
+/// Find the property of `element` named `name`.
+pub fn find_property(element: &dyn Element, name: &str) -> Result<PropertySpec, Error> {
+    element
+        .properties()
+        .into_iter()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| Error::NoSuchProperty(name.to_string()))
+}
+
+/// Read the property `name` of `element`.
+pub(crate) fn get_property(element: &dyn Element, name: &str) -> Result<PropertyValue, Error> {
+    let spec = find_property(element, name)?;
+    if !spec.readable {
+        return Err(Error::PropertyNotReadable(spec.name));
+    }
+
+    element
+        .property(name)
+        .ok_or(Error::NoSuchProperty(spec.name))
+}
+
+/// Write the property `name` of `element`, which is in `state`, after checking that it can be
+/// written and that `value` fits.
+pub(crate) fn set_property(
+    element: &mut dyn Element,
+    state: State,
+    name: &str,
+    value: PropertyValue,
+) -> Result<(), Error> {
+    let spec = find_property(element, name)?;
+    if !spec.writable {
+        return Err(Error::PropertyNotWritable(spec.name));
+    }
+    if state > spec.mutable_in {
+        return Err(Error::PropertyNotMutable(spec.name, state));
+    }
+
+    let value = spec.check(value)?;
+    element.set_property(name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let spec = PropertySpec::int("block-size", "", 1, 1024, 512);
+        assert_eq!(spec.check(1024.into()).unwrap(), PropertyValue::Int(1024));
+        assert!(spec.check(0.into()).is_err());
+        assert!(spec.check("512".into()).is_err());
+        assert_eq!(spec.parse("42").unwrap(), PropertyValue::Int(42));
+
+        let spec = PropertySpec::enumeration("leaky", "", &["no", "upstream"], "no");
+        assert_eq!(
+            spec.check("upstream".into()).unwrap(),
+            PropertyValue::Enum(String::from("upstream"))
+        );
+        assert!(spec.parse("downstream").is_err());
+
+        let spec = PropertySpec::double("volume", "", 0.0, 10.0, 1.0);
+        assert_eq!(spec.parse("2").unwrap(), PropertyValue::Double(2.0));
+        assert!(spec.check(11.into()).is_err());
+    }
+}
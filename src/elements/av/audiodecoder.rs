@@ -13,169 +13,272 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::time::Duration;
+
 use crate::{
+    caps::{self, Caps, Structure},
     element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error, info,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    element_traits::{
+        base::{self, Transform},
+        Element, ElementArchitecture, PadDirection, PadTemplate,
+    },
+    pipeline::{
+        audio::{AudioBuffer, AudioFormat, AudioLayout},
+        error::Error,
+        event::Event,
+        query::Format,
+        Data, ElementContext,
+    },
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-use libav::demuxing::CodecID;
 use libav::{
-    decoding::Decoder,
-    demuxing::{CodecParams, Packet},
+    decoding::{Decoder, Frame},
+    demuxing::{CodecID, CodecParams, Packet, Stream},
 };
 
 ///```text
 ///               +-----------------------------+
 ///               |______                  _____|
-/// AVPacket ---->| sink |  AudioDecoder  | src |----> // TODO
+/// AVPacket ---->| sink |  AudioDecoder  | src |----> AudioBuffer
 ///               |^^^^^^                  ^^^^^|
 ///               +-----------------------------+
 ///```
+///
+/// Decodes the stream it was created with, or opens a decoder with the codec parameters in the
+/// caps of the packets, see [`AV_PACKET`](caps::AV_PACKET).
+///
+/// Frames from before the start of the current segment are dropped. The frames the decoder
+/// still holds at the end of the stream are pushed before the EOS.
 pub struct AudioDecoder {
-    sink: SinkPipe,
-    parent: Parent,
     stream_index: i32,
-    decoder: Decoder,
+    codec_id: Option<CodecID>,
+    decoder: Option<Decoder>,
+    /// The decoded format as far as the codec parameters know it.
+    src_caps: Caps,
+    time_base: Option<(i64, i64)>,
+    segment_start: Duration,
 }
 
-impl AudioDecoder {
-    pub fn new(
-        (stream_index, codec_id, params): (i32, CodecID, CodecParams),
-    ) -> Result<Self, Error> {
-        let decoder =
-            Decoder::new((stream_index, codec_id, params)).map_err(|e| Error::AVError(e))?;
-
-        Ok(Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
-            stream_index,
-            decoder,
-        })
-    }
-
-    /// Link the sink element.
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::TextSink {
-            return Err(Error::InvalidSinkType);
+impl Default for AudioDecoder {
+    fn default() -> Self {
+        Self {
+            stream_index: -1,
+            codec_id: None,
+            decoder: None,
+            src_caps: caps::audio_raw(),
+            time_base: None,
+            segment_start: Duration::ZERO,
         }
+    }
+}
 
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::Text {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
-        }
+impl AudioDecoder {
+    /// A decoder for `stream` of a [`Demuxer`](libav::demuxing::Demuxer).
+    pub fn new(stream: Stream) -> Result<Self, Error> {
+        let mut audiodecoder = Self::default();
+        audiodecoder.open_params(stream.info.index, &stream.params)?;
 
-        Err(Error::InvalidSinkType)
+        Ok(audiodecoder)
     }
 
     pub fn get_stream_index(&self) -> i32 {
         self.stream_index
     }
 
-    fn init(&mut self) -> Result<(), Error> {
-        // let (datagram_sender, datagram_receiver) = bounded(0);
-        // let (msg_sender, my_msg_receiver) = unbounded();
-        // let parent = Parent::new(msg_sender);
-        // let mut sink_element = self.sink.take_element()?;
-        // sink_element.set_parent(parent);
-        // let datagram_receiver_clone = datagram_receiver.clone();
+    fn decoder(&self) -> Result<&Decoder, Error> {
+        self.decoder.as_ref().ok_or(Error::ElementNotReady)
+    }
 
-        // self.sink.thread_handle = Some(std::thread::spawn(move || {
-        //     match sink_element.run(datagram_receiver_clone) {
-        //         Ok(_) => {}
-        //         Err(e) => error!("{e}"),
-        //     }
-        // }));
-        // self.sink.msg_receiver = Some(my_msg_receiver);
-        // self.sink.datagram_sender = Some(datagram_sender);
+    /// Open the decoder with the codec parameters in `caps`.
+    fn open(&mut self, caps: &Caps) -> Result<(), Error> {
+        let Some(structure) = caps.structure() else {
+            return Err(Error::InvalidData("No caps for the packets".to_string()));
+        };
+        let params = super::codec_params(structure)?;
+        let stream_index = structure.get_int("stream-index").unwrap_or(-1) as i32;
+        self.open_params(stream_index, &params)
+    }
+
+    fn open_params(&mut self, stream_index: i32, params: &CodecParams) -> Result<(), Error> {
+        let mut structure = Structure::new(caps::AUDIO_RAW);
+        if let Some(sample_format) = params.sample_format() {
+            structure.set("format", sample_format.as_str());
+        }
+        if params.sample_rate() > 0 {
+            structure.set("rate", params.sample_rate());
+        }
+        if params.channels() > 0 {
+            structure.set("channels", params.channels());
+        }
+
+        self.decoder = Some(Decoder::new(stream_index, params).map_err(Error::AVError)?);
+        self.stream_index = stream_index;
+        self.codec_id = Some(params.codec_id());
+        self.src_caps = Caps::new(structure);
 
         Ok(())
     }
 
-    fn run_loop(&mut self, packet: Packet) -> bool {
-        match self.decoder.decode_packet(packet) {
-            Ok(frames) => {
-                for frame in frames {
-                    info!("Received frame. PTS: {}", frame.get_pts());
-                }
+    fn decode(&mut self, packet: Packet) -> Result<Vec<Data>, Error> {
+        let frames = self
+            .decoder()?
+            .decode_packet(packet)
+            .map_err(Error::AVError)?;
+        self.convert(frames)
+    }
+
+    /// Copy `frames` out of libav, dropping the ones before the segment.
+    fn convert(&self, frames: Vec<Frame>) -> Result<Vec<Data>, Error> {
+        super::convert_frames(frames, self.time_base, self.segment_start, |frame| {
+            Ok(Data::AudioBuffer(self.audio_buffer(frame)?))
+        })
+    }
+
+    /// Copy the samples of `frame` out of libav.
+    fn audio_buffer(&self, frame: &Frame) -> Result<AudioBuffer, Error> {
+        let Some(sample_format) = frame.sample_format() else {
+            return Err(Error::InvalidData(
+                "Decoded frame has no sample format".to_string(),
+            ));
+        };
+
+        Ok(AudioBuffer {
+            format: AudioFormat {
+                sample_format,
+                sample_rate: frame.sample_rate(),
+                channels: frame.channels(),
+                channel_layout: frame.channel_layout().unwrap_or_default(),
+                layout: if frame.is_planar() {
+                    AudioLayout::Planar
+                } else {
+                    AudioLayout::Interleaved
+                },
+            },
+            samples: frame.samples(),
+            planes: frame
+                .audio_planes()
+                .into_iter()
+                .map(<[u8]>::to_vec)
+                .collect(),
+            pts: frame
+                .pts()
+                .and_then(|pts| super::ticks_to_duration(self.time_base, pts)),
+        })
+    }
+}
+
+impl Transform for AudioDecoder {
+    fn transform(&mut self, data: Data) -> Result<Vec<Data>, Error> {
+        match data {
+            Data::AVPacket(packet) => self.decode(packet),
+            _ => Err(Error::ReceivedInvalidDatagramFromParent),
+        }
+    }
+
+    fn sink_event(&mut self, event: &Event) -> Result<Vec<Data>, Error> {
+        match event {
+            Event::FlushStart => self.decoder()?.flush(),
+            Event::Segment(segment) if segment.format == Format::Time => {
+                self.segment_start = Duration::from_nanos(segment.start)
             }
-            Err(e) => {
-                error!("{e}");
-                return false;
+            // The last frames are pushed before the EOS
+            Event::Eos => {
+                let frames = self.decoder()?.drain().map_err(Error::AVError)?;
+                return self.convert(frames);
             }
+            _ => {}
         }
 
-        true
+        Ok(Vec::new())
     }
 }
 
 impl Element for AudioDecoder {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::AVPacketAudioSink
-    }
-
     fn get_architecture(&self) -> ElementArchitecture {
         ElementArchitecture {
-            sink: Sink::One(CommonFormat::AVPacket),
-            srcs: Srcs::One(CommonFormat::Text),
+            sinks: vec![PadTemplate::always("sink", caps::av_packet("audio"))],
+            srcs: vec![PadTemplate::always("src", caps::audio_raw())],
         }
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
+    fn query_caps(&self, direction: PadDirection, pad: &str) -> Caps {
+        match (direction, pad) {
+            // Only packets of the codec the decoder was opened with can be decoded
+            (PadDirection::Sink, "sink") => match self.codec_id {
+                Some(codec_id) => caps::av_packet("audio").intersect(&Caps::new(
+                    Structure::new(caps::AV_PACKET).field("codec-id", codec_id),
+                )),
+                None => caps::av_packet("audio"),
+            },
+            (PadDirection::Src, "src") => self.src_caps.clone(),
+            _ => Caps::empty(),
+        }
+    }
 
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                Datagram::Data(data) => match data {
-                    Data::AVPacket(packet) => {
-                        if !self.run_loop(packet) {
-                            break;
-                        }
-                    }
-                    _ => {
-                        error!("Received invalid data type");
-                        break;
-                    }
-                },
+    fn set_caps(&mut self, direction: PadDirection, pad: &str, caps: &Caps) -> Result<(), Error> {
+        if (direction, pad) == (PadDirection::Sink, "sink") {
+            self.time_base = caps
+                .structure()
+                .and_then(|structure| structure.get_fraction("time-base"));
+            if self.decoder.is_none() {
+                self.open(caps)?;
             }
-
-            // while let Some(_msg) = self.sink.try_recv_msg()? {
-            //     // TODO: Handle messages
-            // }
         }
 
-        self.parent.send_finished()
+        Ok(())
     }
 
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
-    }
-
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
-
-        self.sink.join_thread()
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_transform(self, ctx)
     }
 }
 
 element_def! {
     AudioDecoder,
-    "audiodecoder"
+    "audiodecoder",
+    "Codec/Decoder/Audio",
+    "Decodes compressed audio packets"
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "element-demuxsrc", feature = "element-testsink"))]
 mod tests {
     use super::*;
+    use crate::elements::av::{test_util, ResourceLocation};
+    use crate::pipeline::Datagram;
+    use libav::demuxing::Demuxer;
+
+    #[test]
+    fn test_decode() {
+        let path = test_util::write_wav("streamcraft_test_audiodecoder.wav", 8000);
+        let stream = Demuxer::new(ResourceLocation::new_file(path.clone()))
+            .unwrap()
+            .get_audio_stream()
+            .unwrap();
+        let audiodecoder = AudioDecoder::new(stream).unwrap();
+        assert_eq!(
+            audiodecoder.query_caps(PadDirection::Src, "src"),
+            Caps::new(
+                Structure::new(caps::AUDIO_RAW)
+                    .field("format", "s16")
+                    .field("rate", 8000)
+                    .field("channels", 1)
+            )
+        );
+
+        let received = test_util::decode(path, "audio", audiodecoder, caps::audio_raw());
+        let mut samples = 0;
+        for datagram in received {
+            let Datagram::Data(data) = datagram else {
+                continue;
+            };
+            let Data::AudioBuffer(buffer) = data else {
+                panic!("Expected an audio buffer, got {data:?}");
+            };
+            assert_eq!(buffer.format.sample_format, "s16");
+            assert_eq!(buffer.format.sample_rate, 8000);
+            assert_eq!(buffer.format.channels, 1);
+            samples += buffer.samples;
+        }
+        assert_eq!(samples, 8000);
+    }
 }
@@ -13,243 +13,358 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::time::Duration;
+
 use crate::{
+    caps::{self, Caps, Value},
     debug, element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error, info,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    element_traits::{
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadDirection, PadPresence, PadTemplate,
+    },
+    pipeline::{
+        error::Error,
+        event::{Event, Seek, Segment},
+        query::{Format, Query},
+        state::{State, StateChange},
+        Data, Datagram, ElementContext, Input, Message,
+    },
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-use libav::demuxing::{CodecID, CodecParams, Demuxer, ResourceLocation};
-
+use libav::demuxing::{CodecID, Demuxer, ResourceLocation, Stream};
+
+const VIDEO_SRC: &str = "video";
+const AUDIO_SRC: &str = "audio";
+
+/// Demuxes either the input it was created with, or the file at `location`. The streams of the
+/// file are looked up when the location is set so that the pads can be linked, and the file is
+/// opened when going to [`State::Ready`].
+///
+/// The `video` and `audio` pads only exist if the input has a stream of that type. The input
+/// is closed in the Null state and opened again from the start when going back to Ready.
+///
+/// Handles seeks in [`Format::Time`]. The segment after a key unit seek starts at the keyframe,
+/// after an accurate seek it starts at the requested position.
+///
 ///```text
-/// +------------------+
-/// |             _____|
-/// |            | src |----> AVPacket
-/// |  DemuxSrc   -----|
-/// |            | src |----> AVPacket
-/// |             ^^^^^|
-/// +------------------+
+/// +--------------------+
+/// |             _______|
+/// |            | video |----> AVPacket
+/// |  DemuxSrc   -------|
+/// |            | audio |----> AVPacket
+/// |             ^^^^^^^|
+/// +--------------------+
 ///```
 pub struct DemuxSrc {
-    demuxer: Demuxer,
-    video_sink: SinkPipe,
+    resource: Option<ResourceLocation>,
+    demuxer: Option<Demuxer>,
     video_stream_index: i32,
-    audio_sink: SinkPipe,
+    video_codec_id: Option<CodecID>,
     audio_stream_index: i32,
-    parent: Parent,
+    audio_codec_id: Option<CodecID>,
+    /// Time of the last packet that was read.
+    position: Option<Duration>,
+    last_seqnum: Option<u32>,
+    /// The seek to send a segment for before the next packet.
+    pending_segment: Option<Seek>,
 }
 
-impl DemuxSrc {
-    pub fn new(resource: ResourceLocation) -> Result<Self, Error> {
-        let demuxer = Demuxer::new(resource).map_err(|e| Error::AVError(e))?;
-
-        Ok(Self {
-            demuxer,
-            audio_sink: SinkPipe::default(),
+impl Default for DemuxSrc {
+    fn default() -> Self {
+        Self {
+            resource: None,
+            demuxer: None,
             video_stream_index: -1,
-            video_sink: SinkPipe::default(),
+            video_codec_id: None,
             audio_stream_index: -1,
-            parent: Parent::default(),
-        })
+            audio_codec_id: None,
+            position: None,
+            last_seqnum: None,
+            pending_segment: None,
+        }
     }
+}
 
-    pub fn link_video_sink_element(
-        &mut self,
-        stream_index: i32,
-        sink: impl Element + 'static,
-    ) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::AVPacketVideoSink {
-            return Err(Error::InvalidSinkType);
-        }
+impl DemuxSrc {
+    pub fn new(resource: ResourceLocation) -> Result<Self, Error> {
+        let mut demuxsrc = Self {
+            resource: Some(resource),
+            ..Self::default()
+        };
+        demuxsrc.open()?;
 
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::AVPacket {
-                self.video_sink.set_element(sink);
-                self.video_stream_index = stream_index;
-                return Ok(());
+        Ok(demuxsrc)
+    }
+
+    /// Set the path of the file to demux.
+    pub fn set_location(&mut self, location: &str) {
+        self.resource = Some(ResourceLocation::new_file(location.into()));
+        self.demuxer = None;
+        match Demuxer::new(ResourceLocation::new_file(location.into())) {
+            Ok(demuxer) => self.find_streams(&demuxer),
+            Err(e) => {
+                // Reported again when the file is opened
+                debug!("Failed to look up the streams of {location}: {e}");
+                self.video_stream_index = -1;
+                self.audio_stream_index = -1;
             }
         }
+    }
 
-        Err(Error::InvalidSinkType)
+    fn demuxer(&self) -> Result<&Demuxer, Error> {
+        self.demuxer.as_ref().ok_or(Error::ElementNotReady)
     }
 
-    pub fn link_audio_sink_element(
-        &mut self,
-        stream_index: i32,
-        sink: impl Element + 'static,
-    ) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::AVPacketAudioSink {
-            return Err(Error::InvalidSinkType);
-        }
+    fn open(&mut self) -> Result<(), Error> {
+        let Some(resource) = self.resource.clone() else {
+            return Err(Error::FailedToOpen("No location set".to_string()));
+        };
+        let demuxer = Demuxer::new(resource).map_err(Error::AVError)?;
+        self.find_streams(&demuxer);
+        self.demuxer = Some(demuxer);
 
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::AVPacket {
-                self.audio_sink.set_element(sink);
-                self.audio_stream_index = stream_index;
-                return Ok(());
-            }
-        }
+        Ok(())
+    }
 
-        Err(Error::InvalidSinkType)
+    fn find_streams(&mut self, demuxer: &Demuxer) {
+        (self.video_stream_index, self.video_codec_id) = match demuxer.get_video_stream() {
+            Ok(stream) => (stream.info.index, Some(stream.info.codec_id)),
+            Err(_) => (-1, None),
+        };
+        (self.audio_stream_index, self.audio_codec_id) = match demuxer.get_audio_stream() {
+            Ok(stream) => (stream.info.index, Some(stream.info.codec_id)),
+            Err(_) => (-1, None),
+        };
     }
 
-    pub fn get_video_stream(&self) -> Result<(i32, CodecID, CodecParams), Error> {
-        self.demuxer
-            .get_video_stream()
-            .map_err(|e| Error::AVError(e))
+    pub fn get_video_stream(&self) -> Result<Stream, Error> {
+        self.demuxer()?.get_video_stream().map_err(Error::AVError)
     }
 
-    pub fn get_audio_stream(&self) -> Result<(i32, CodecID, CodecParams), Error> {
-        self.demuxer
-            .get_audio_stream()
-            .map_err(|e| Error::AVError(e))
+    pub fn get_audio_stream(&self) -> Result<Stream, Error> {
+        self.demuxer()?.get_audio_stream().map_err(Error::AVError)
     }
 
-    fn run_loop(&mut self) -> bool {
-        match self.demuxer.read_frame() {
+    /// Returns `false` when the input has ended.
+    fn run_loop(&mut self, ctx: &ElementContext) -> Result<bool, Error> {
+        let Some(demuxer) = self.demuxer.as_mut() else {
+            return Err(Error::ElementNotReady);
+        };
+
+        match demuxer.read_frame() {
             Ok(packet) => {
+                let time = demuxer.packet_time(&packet);
+                if let Some(seek) = self.pending_segment.take() {
+                    let requested = Duration::from_nanos(seek.position);
+                    let start = match time {
+                        Some(time) if !seek.flags.accurate => time,
+                        _ => requested,
+                    };
+                    let segment = Segment::time(start, None);
+                    ctx.push_event(Event::Segment(segment))?;
+                }
+                if time.is_some() {
+                    self.position = time;
+                }
                 let stream_index = packet.stream_index();
-                if stream_index == self.audio_stream_index {
-                    info!("Got audio packet");
-                    if let Err(e) = self
-                        .audio_sink
-                        .send_datagram(Datagram::Data(Data::AVPacket(packet)))
-                    {
-                        error!("{e}");
-                        return false;
-                    }
+                let src = if stream_index == self.audio_stream_index {
+                    debug!("Got audio packet");
+                    AUDIO_SRC
                 } else if stream_index == self.video_stream_index {
-                    info!("Got video packet");
-                    if let Err(e) = self
-                        .video_sink
-                        .send_datagram(Datagram::Data(Data::AVPacket(packet)))
-                    {
-                        error!("{e}");
-                        return false;
-                    }
+                    debug!("Got video packet");
+                    VIDEO_SRC
+                } else {
+                    return Ok(true);
+                };
+
+                // Packets for pads that are not linked are dropped
+                if ctx.src(src).is_ok() {
+                    ctx.push(src, Datagram::Data(Data::AVPacket(packet)))?;
                 }
             }
-            Err(e) => {
-                error!("{e}");
-                return false;
+            Err(e) if e.is_eof() => {
+                ctx.push_event(Event::Eos)?;
+                return Ok(false);
             }
+            Err(e) => return Err(Error::AVError(e)),
         }
 
-        true
+        Ok(true)
     }
+}
 
-    fn init(&mut self) -> Result<(), Error> {
-        // Video
-        {
-            let (datagram_sender, datagram_receiver) = bounded(0);
-            let (msg_sender, my_msg_receiver) = unbounded();
-            let parent = Parent::new(msg_sender);
-            let mut sink_element = self.video_sink.take_element()?;
-            sink_element.set_parent(parent);
-            let datagram_receiver_clone = datagram_receiver.clone();
-
-            self.video_sink.thread_handle = Some(std::thread::spawn(move || {
-                match sink_element.run(datagram_receiver_clone) {
-                    Ok(_) => {}
-                    Err(e) => error!("Error occurred running sink element: {e}"),
-                }
-            }));
-            self.video_sink.msg_receiver = Some(my_msg_receiver);
-            self.video_sink.datagram_sender = Some(datagram_sender);
+impl Element for DemuxSrc {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: Vec::new(),
+            srcs: vec![
+                PadTemplate::new(VIDEO_SRC, PadPresence::Sometimes, caps::av_packet("video")),
+                PadTemplate::new(AUDIO_SRC, PadPresence::Sometimes, caps::av_packet("audio")),
+            ],
         }
+    }
 
-        // Audio
-        {
-            let (datagram_sender, datagram_receiver) = bounded(0);
-            let (msg_sender, my_msg_receiver) = unbounded();
-            let parent = Parent::new(msg_sender);
-            let mut sink_element = self.audio_sink.take_element()?;
-            sink_element.set_parent(parent);
-            let datagram_receiver_clone = datagram_receiver.clone();
-
-            self.audio_sink.thread_handle = Some(std::thread::spawn(move || {
-                match sink_element.run(datagram_receiver_clone) {
-                    Ok(_) => {}
-                    Err(e) => error!("Error occurred running sink element: {e}"),
-                }
-            }));
-            self.audio_sink.msg_receiver = Some(my_msg_receiver);
-            self.audio_sink.datagram_sender = Some(datagram_sender);
+    fn properties(&self) -> Vec<PropertySpec> {
+        vec![PropertySpec::string(
+            "location",
+            "Path of the file to demux, opened when set to look up its streams",
+            "",
+        )
+        .mutable_in(State::Null)]
+    }
+
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        match name {
+            "location" => Some(PropertyValue::Str(match &self.resource {
+                Some(ResourceLocation::File(path)) => path.to_string_lossy().into_owned(),
+                None => String::new(),
+            })),
+            _ => None,
+        }
+    }
+
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value) {
+            ("location", PropertyValue::Str(location)) => self.set_location(&location),
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
         }
 
         Ok(())
     }
-}
 
-impl Element for DemuxSrc {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::AVPacketSrc
+    fn has_pad(&self, name: &str) -> bool {
+        match name {
+            VIDEO_SRC => self.video_stream_index >= 0,
+            AUDIO_SRC => self.audio_stream_index >= 0,
+            _ => false,
+        }
     }
 
-    fn get_architecture(&self) -> ElementArchitecture {
-        ElementArchitecture {
-            sink: Sink::None,
-            srcs: Srcs::Two((CommonFormat::AVPacket, CommonFormat::AVPacket)),
+    fn query_caps(&self, direction: PadDirection, pad: &str) -> Caps {
+        let (media_type, codec_id) = match (direction, pad) {
+            (PadDirection::Src, VIDEO_SRC) => ("video", self.video_codec_id),
+            (PadDirection::Src, AUDIO_SRC) => ("audio", self.audio_codec_id),
+            _ => return Caps::empty(),
+        };
+
+        let (Some(codec_id), Some(demuxer)) = (codec_id, &self.demuxer) else {
+            return Caps::empty();
+        };
+        let stream_index = match pad {
+            VIDEO_SRC => self.video_stream_index,
+            _ => self.audio_stream_index,
+        };
+        let mut structure = caps::Structure::new(caps::AV_PACKET)
+            .field("codec-id", codec_id)
+            .field("stream-index", stream_index);
+        if let Ok(stream) = demuxer.get_stream(stream_index) {
+            super::set_codec_fields(&mut structure, &stream.params);
+        }
+        if let Some((num, den)) = demuxer.time_base(stream_index) {
+            structure.set("time-base", Value::Fraction(num.into(), den.into()));
         }
+
+        caps::av_packet(media_type).intersect(&Caps::new(structure))
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
+    fn upstream_event(
+        &mut self,
+        ctx: &mut ElementContext,
+        _pad: &str,
+        event: &Event,
+    ) -> Result<bool, Error> {
+        let Event::Seek(seek) = event else {
+            return Ok(false);
+        };
+        if seek.format != Format::Time {
+            return Ok(false);
+        }
+        // The same seek arrives through every linked pad
+        if self.last_seqnum == Some(seek.seqnum) {
+            return Ok(true);
+        }
+        self.last_seqnum = Some(seek.seqnum);
 
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Iter => {
-                        if !self.run_loop() {
-                            break;
-                        }
-                        self.parent.send_iter_fin()?;
-                    }
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-            }
+        ctx.push_event(Event::FlushStart)?;
+        self.demuxer()?
+            .seek(Duration::from_nanos(seek.position))
+            .map_err(Error::AVError)?;
+        ctx.push_event(Event::FlushStop)?;
+        self.pending_segment = Some(seek.clone());
+
+        Ok(true)
+    }
 
-            while let Some(_msg) = self.video_sink.try_recv_msg()? {
-                // TODO: Handle messages
+    fn query(&mut self, _direction: PadDirection, _pad: &str, query: &mut Query) -> bool {
+        match query {
+            Query::Position {
+                format: Format::Time,
+                position,
+            } => *position = self.position.map(|time| time.as_nanos() as u64),
+            Query::Duration {
+                format: Format::Time,
+                duration,
+            } => {
+                *duration = self
+                    .demuxer
+                    .as_ref()
+                    .and_then(Demuxer::duration)
+                    .map(|time| time.as_nanos() as u64)
             }
-            while let Some(_msg) = self.audio_sink.try_recv_msg()? {
-                // TODO: Handle messages
+            Query::Seeking {
+                format: Format::Time,
+                seekable,
+                start,
+                end,
+            } => {
+                *seekable = true;
+                *start = 0;
+                *end = self
+                    .demuxer
+                    .as_ref()
+                    .and_then(Demuxer::duration)
+                    .map(|time| time.as_nanos() as u64);
             }
+            // Not live and no latency, which is what the query starts out with
+            Query::Latency { .. } => {}
+            _ => return false,
         }
 
-        self.parent.send_finished()
-    }
-
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
+        true
     }
 
-    fn cleanup(&mut self) -> Result<(), Error> {
-        if self.video_sink.is_operational() {
-            if let Err(e) = self.video_sink.send_quit() {
-                error!("Failed to send quit to video sink: {e}");
+    fn change_state(&mut self, change: StateChange) -> Result<(), Error> {
+        match change {
+            StateChange::NullToReady if self.demuxer.is_none() => self.open(),
+            // Start over from the beginning of the input
+            StateChange::PausedToReady => {
+                self.position = None;
+                self.pending_segment = None;
+                self.open()
             }
-            self.video_sink.drop_data_sender();
-        }
-        if self.audio_sink.is_operational() {
-            if let Err(e) = self.audio_sink.send_quit() {
-                error!("Failed to send quit to audio sink: {e}");
+            StateChange::ReadyToNull => {
+                self.demuxer = None;
+                Ok(())
             }
-            self.audio_sink.drop_data_sender();
+            _ => Ok(()),
         }
+    }
 
-        if self.video_sink.is_operational() {
-            self.video_sink.join_thread()?;
-        }
-        if self.audio_sink.is_operational() {
-            self.audio_sink.join_thread()?;
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        ctx.push_event(Event::StreamStart)?;
+        ctx.push_event(Event::Segment(Segment::default()))?;
+
+        loop {
+            match ctx.recv(self)? {
+                Input::Control(Message::Iter) => {
+                    if !self.run_loop(ctx)? {
+                        break;
+                    }
+                    ctx.iter_fin()?;
+                }
+                Input::Control(Message::Quit) => break,
+                _ => return Err(Error::ReceivedInvalidDatagramFromParent),
+            }
         }
 
         Ok(())
@@ -258,10 +373,7 @@ impl Element for DemuxSrc {
 
 element_def! {
     DemuxSrc,
-    "demuxsrc"
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    "demuxsrc",
+    "Source/Demuxer",
+    "Reads the packets of the video and audio streams of a file"
 }
@@ -15,6 +15,128 @@
 
 pub use libav::demuxing::ResourceLocation;
 
+#[cfg(feature = "element-audiodecoder")]
 pub mod audiodecoder;
+#[cfg(feature = "element-demuxsrc")]
 pub mod demuxsrc;
+#[cfg(feature = "element-videodecoder")]
 pub mod videodecoder;
+
+#[cfg(all(test, feature = "element-demuxsrc", feature = "element-testsink"))]
+mod test_util;
+
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+use std::time::Duration;
+
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+use libav::decoding::Frame;
+use libav::demuxing::CodecParams;
+
+use crate::{caps::Structure, define_log_info};
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+use crate::{
+    debug, info,
+    pipeline::{error::Error, Data},
+};
+
+/// Describe `params` with the fields of [`AV_PACKET`](crate::caps::AV_PACKET) caps, so that a
+/// decoder can be opened from the caps with [`codec_params`].
+#[cfg(feature = "element-demuxsrc")]
+pub(crate) fn set_codec_fields(structure: &mut Structure, params: &CodecParams) {
+    structure.set("codec-id", params.codec_id());
+    if !params.extradata().is_empty() {
+        structure.set("codec-data", params.extradata());
+    }
+    if let Some(format) = params.format() {
+        structure.set("format", format);
+    }
+    let (width, height) = params.dimensions();
+    let fields = [
+        ("width", width.into()),
+        ("height", height.into()),
+        ("sample-rate", params.sample_rate().into()),
+        ("channels", params.channels().into()),
+        ("bit-rate", params.bit_rate()),
+        ("block-align", params.block_align().into()),
+        (
+            "bits-per-coded-sample",
+            params.bits_per_coded_sample().into(),
+        ),
+    ];
+    // 0 is unknown
+    for (name, value) in fields.into_iter().filter(|(_, value)| *value > 0) {
+        structure.set(name, value);
+    }
+}
+
+/// Get the codec parameters that [`set_codec_fields`] described.
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+pub(crate) fn codec_params(structure: &Structure) -> Result<CodecParams, Error> {
+    let Some(codec_id) = structure.get_int("codec-id") else {
+        return Err(Error::InvalidData("No codec-id in the caps".to_string()));
+    };
+
+    let mut params =
+        CodecParams::new(codec_id as libav::demuxing::CodecID).map_err(Error::AVError)?;
+    if let Some(codec_data) = structure.get_bytes("codec-data") {
+        params.set_extradata(codec_data).map_err(Error::AVError)?;
+    }
+    let int = |name| structure.get_int(name).unwrap_or_default();
+    if let Some(format) = structure.get_int("format") {
+        params.set_format(format as i32);
+    }
+    params.set_dimensions(int("width") as u32, int("height") as u32);
+    params.set_sample_rate(int("sample-rate") as u32);
+    if int("channels") > 0 {
+        params.set_channels(int("channels") as u32);
+    }
+    params.set_bit_rate(int("bit-rate"));
+    params.set_block_align(int("block-align") as u32);
+    params.set_bits_per_coded_sample(int("bits-per-coded-sample") as u32);
+
+    Ok(params)
+}
+
+/// Convert `ticks` in the time base of a stream, `None` if either is unknown.
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+pub(crate) fn ticks_to_duration(time_base: Option<(i64, i64)>, ticks: i64) -> Option<Duration> {
+    let (num, den) = time_base?;
+    if ticks < 0 || num <= 0 || den <= 0 {
+        return None;
+    }
+
+    let nanos = ticks as u128 * num as u128 * 1_000_000_000 / den as u128;
+    Some(Duration::from_nanos(nanos as u64))
+}
+
+/// Copy decoded `frames` out of libav with `copy`, dropping the ones from before the start of
+/// the segment.
+#[cfg(any(feature = "element-videodecoder", feature = "element-audiodecoder"))]
+pub(crate) fn convert_frames(
+    frames: Vec<Frame>,
+    time_base: Option<(i64, i64)>,
+    segment_start: Duration,
+    copy: impl Fn(&Frame) -> Result<Data, Error>,
+) -> Result<Vec<Data>, Error> {
+    let mut decoded = Vec::new();
+    for frame in frames {
+        match frame
+            .pts()
+            .and_then(|pts| ticks_to_duration(time_base, pts))
+        {
+            Some(time) if time < segment_start => {
+                debug!("Dropping frame before the segment. PTS: {time:?}");
+            }
+            pts => {
+                info!("Decoded frame. PTS: {pts:?}");
+                decoded.push(copy(&frame)?);
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+define_log_info! {
+    "av"
+}
@@ -0,0 +1,106 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers for the tests of the av elements.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    caps::Caps,
+    element_traits::Element,
+    elements::{av::demuxsrc::DemuxSrc, misc::testsink::TestSink},
+    pipeline::{graph::PipelineGraph, Datagram, Pipeline},
+};
+
+use super::ResourceLocation;
+
+/// Write a 4x2 YUV 4:2:0 y4m file with `frames` frames at 25 fps to the temp dir.
+pub fn write_y4m(name: &str, frames: u8) -> PathBuf {
+    let mut contents = b"YUV4MPEG2 W4 H2 F25:1 Ip A1:1 C420jpeg\n".to_vec();
+    for i in 0..frames {
+        contents.extend_from_slice(b"FRAME\n");
+        // 8 bytes luma, 2 bytes for each chroma plane
+        contents.extend_from_slice(&[i; 12]);
+    }
+
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Write a mono 8 kHz 16 bit WAV file with `samples` samples to the temp dir.
+pub fn write_wav(name: &str, samples: u32) -> PathBuf {
+    let data_size = samples * 2;
+    let mut contents = Vec::new();
+    contents.extend_from_slice(b"RIFF");
+    contents.extend_from_slice(&(36 + data_size).to_le_bytes());
+    contents.extend_from_slice(b"WAVEfmt ");
+    contents.extend_from_slice(&16u32.to_le_bytes());
+    contents.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    contents.extend_from_slice(&1u16.to_le_bytes()); // channels
+    contents.extend_from_slice(&8000u32.to_le_bytes()); // sample rate
+    contents.extend_from_slice(&16000u32.to_le_bytes()); // byte rate
+    contents.extend_from_slice(&2u16.to_le_bytes()); // block align
+    contents.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    contents.extend_from_slice(b"data");
+    contents.extend_from_slice(&data_size.to_le_bytes());
+    contents.resize(contents.len() + data_size as usize, 0);
+
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Demux the file at `path`, play its `pad` through `decoder` and get the data and events that
+/// came out, in order.
+pub fn decode(
+    path: PathBuf,
+    pad: &str,
+    decoder: impl Element + 'static,
+    raw_caps: Caps,
+) -> Vec<Datagram> {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = Arc::clone(&received);
+    let mut testsink = TestSink::new(
+        raw_caps,
+        |_, _| true,
+        move |_, data| {
+            received_clone.lock().unwrap().push(Datagram::Data(data));
+            true
+        },
+    );
+    let received_clone = Arc::clone(&received);
+    testsink.set_on_event(move |event| {
+        received_clone.lock().unwrap().push(Datagram::Event(event));
+    });
+
+    let mut graph = PipelineGraph::new();
+    let demuxsrc = graph.add_element(DemuxSrc::new(ResourceLocation::new_file(path)).unwrap());
+    let decoder = graph.add_element(decoder);
+    let testsink = graph.add_element(testsink);
+    graph.link((demuxsrc, pad), (decoder, "sink")).unwrap();
+    graph.link((decoder, "src"), (testsink, "sink")).unwrap();
+
+    let mut pipeline = Pipeline::new(graph);
+    pipeline.play().unwrap();
+    pipeline.wait_eos().unwrap();
+    drop(pipeline);
+
+    let received = std::mem::take(&mut *received.lock().unwrap());
+    received
+}
@@ -13,168 +13,295 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::time::Duration;
+
 use crate::{
+    caps::{self, Caps, Structure},
     element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error, info,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    element_traits::{
+        base::{self, Transform},
+        Element, ElementArchitecture, PadDirection, PadTemplate,
+    },
+    pipeline::{
+        error::Error,
+        event::Event,
+        query::Format,
+        video::{VideoFormat, VideoFrame, VideoPlane},
+        Data, ElementContext,
+    },
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
 use libav::{
-    decoding::Decoder,
-    demuxing::{CodecID, CodecParams, Packet},
+    decoding::{Decoder, Frame},
+    demuxing::{CodecID, CodecParams, Packet, Stream},
 };
 
 ///```text
 ///               +-----------------------------+
 ///               |______                  _____|
-/// AVPacket ---->| sink |  VideoDecoder  | src |----> // TODO
+/// AVPacket ---->| sink |  VideoDecoder  | src |----> VideoFrame
 ///               |^^^^^^                  ^^^^^|
 ///               +-----------------------------+
 ///```
+///
+/// Decodes the stream it was created with, or opens a decoder with the codec parameters in the
+/// caps of the packets, see [`AV_PACKET`](caps::AV_PACKET).
+///
+/// Frames from before the start of the current segment are dropped. The frames the decoder
+/// still holds at the end of the stream are pushed before the EOS.
 pub struct VideoDecoder {
-    sink: SinkPipe,
-    parent: Parent,
     stream_index: i32,
-    decoder: Decoder,
+    codec_id: Option<CodecID>,
+    decoder: Option<Decoder>,
+    /// The decoded format as far as the codec parameters know it.
+    src_caps: Caps,
+    time_base: Option<(i64, i64)>,
+    segment_start: Duration,
 }
 
-impl VideoDecoder {
-    pub fn new(
-        (stream_index, codec_id, params): (i32, CodecID, CodecParams),
-    ) -> Result<Self, Error> {
-        let decoder =
-            Decoder::new((stream_index, codec_id, params)).map_err(|e| Error::AVError(e))?;
-
-        Ok(Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
-            stream_index,
-            decoder,
-        })
-    }
-
-    /// Link the sink element.
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::TextSink {
-            return Err(Error::InvalidSinkType);
+impl Default for VideoDecoder {
+    fn default() -> Self {
+        Self {
+            stream_index: -1,
+            codec_id: None,
+            decoder: None,
+            src_caps: caps::video_raw(),
+            time_base: None,
+            segment_start: Duration::ZERO,
         }
+    }
+}
 
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::Text {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
-        }
+impl VideoDecoder {
+    /// A decoder for `stream` of a [`Demuxer`](libav::demuxing::Demuxer).
+    pub fn new(stream: Stream) -> Result<Self, Error> {
+        let mut videodecoder = Self::default();
+        videodecoder.open_params(stream.info.index, &stream.params)?;
 
-        Err(Error::InvalidSinkType)
+        Ok(videodecoder)
     }
 
     pub fn get_stream_index(&self) -> i32 {
         self.stream_index
     }
 
-    fn init(&mut self) -> Result<(), Error> {
-        // let (datagram_sender, datagram_receiver) = bounded(0);
-        // let (msg_sender, my_msg_receiver) = unbounded();
-        // let parent = Parent::new(msg_sender);
-        // let mut sink_element = self.sink.take_element()?;
-        // sink_element.set_parent(parent);
-        // let datagram_receiver_clone = datagram_receiver.clone();
+    fn decoder(&self) -> Result<&Decoder, Error> {
+        self.decoder.as_ref().ok_or(Error::ElementNotReady)
+    }
 
-        // self.sink.thread_handle = Some(std::thread::spawn(move || {
-        //     match sink_element.run(datagram_receiver_clone) {
-        //         Ok(_) => {}
-        //         Err(e) => error!("{e}"),
-        //     }
-        // }));
-        // self.sink.msg_receiver = Some(my_msg_receiver);
-        // self.sink.datagram_sender = Some(datagram_sender);
+    /// Open the decoder with the codec parameters in `caps`.
+    fn open(&mut self, caps: &Caps) -> Result<(), Error> {
+        let Some(structure) = caps.structure() else {
+            return Err(Error::InvalidData("No caps for the packets".to_string()));
+        };
+        let params = super::codec_params(structure)?;
+        let stream_index = structure.get_int("stream-index").unwrap_or(-1) as i32;
+        self.open_params(stream_index, &params)
+    }
+
+    fn open_params(&mut self, stream_index: i32, params: &CodecParams) -> Result<(), Error> {
+        let mut structure = Structure::new(caps::VIDEO_RAW);
+        if let Some(pixel_format) = params.pixel_format() {
+            structure.set("format", pixel_format.as_str());
+        }
+        let (width, height) = params.dimensions();
+        if width > 0 && height > 0 {
+            structure.set("width", width);
+            structure.set("height", height);
+        }
+
+        self.decoder = Some(Decoder::new(stream_index, params).map_err(Error::AVError)?);
+        self.stream_index = stream_index;
+        self.codec_id = Some(params.codec_id());
+        self.src_caps = Caps::new(structure);
 
         Ok(())
     }
 
-    fn run_loop(&mut self, packet: Packet) -> bool {
-        match self.decoder.decode_packet(packet) {
-            Ok(frames) => {
-                for frame in frames {
-                    info!("Received frame. PTS: {}", frame.get_pts());
-                }
+    fn decode(&mut self, packet: Packet) -> Result<Vec<Data>, Error> {
+        let frames = self
+            .decoder()?
+            .decode_packet(packet)
+            .map_err(Error::AVError)?;
+        self.convert(frames)
+    }
+
+    /// Copy `frames` out of libav, dropping the ones before the segment.
+    fn convert(&self, frames: Vec<Frame>) -> Result<Vec<Data>, Error> {
+        super::convert_frames(frames, self.time_base, self.segment_start, |frame| {
+            Ok(Data::VideoFrame(self.video_frame(frame)?))
+        })
+    }
+
+    /// Copy the data of `frame` out of libav.
+    fn video_frame(&self, frame: &Frame) -> Result<VideoFrame, Error> {
+        let Some(pixel_format) = frame.pixel_format() else {
+            return Err(Error::InvalidData(
+                "Decoded frame has no pixel format".to_string(),
+            ));
+        };
+
+        Ok(VideoFrame {
+            format: VideoFormat {
+                pixel_format,
+                width: frame.width(),
+                height: frame.height(),
+            },
+            planes: frame
+                .video_planes()
+                .into_iter()
+                .map(|(data, stride)| VideoPlane {
+                    data: data.to_vec(),
+                    stride,
+                })
+                .collect(),
+            pts: frame
+                .pts()
+                .and_then(|pts| super::ticks_to_duration(self.time_base, pts)),
+            duration: frame
+                .duration()
+                .and_then(|duration| super::ticks_to_duration(self.time_base, duration)),
+            keyframe: frame.is_keyframe(),
+            interlaced: frame.is_interlaced(),
+            top_field_first: frame.is_top_field_first(),
+        })
+    }
+}
+
+impl Transform for VideoDecoder {
+    fn transform(&mut self, data: Data) -> Result<Vec<Data>, Error> {
+        match data {
+            Data::AVPacket(packet) => self.decode(packet),
+            _ => Err(Error::ReceivedInvalidDatagramFromParent),
+        }
+    }
+
+    fn sink_event(&mut self, event: &Event) -> Result<Vec<Data>, Error> {
+        match event {
+            Event::FlushStart => self.decoder()?.flush(),
+            Event::Segment(segment) if segment.format == Format::Time => {
+                self.segment_start = Duration::from_nanos(segment.start)
             }
-            Err(e) => {
-                error!("{e}");
-                return false;
+            // The last frames are pushed before the EOS
+            Event::Eos => {
+                let frames = self.decoder()?.drain().map_err(Error::AVError)?;
+                return self.convert(frames);
             }
+            _ => {}
         }
 
-        true
+        Ok(Vec::new())
     }
 }
 
 impl Element for VideoDecoder {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::AVPacketVideoSink
-    }
-
     fn get_architecture(&self) -> ElementArchitecture {
         ElementArchitecture {
-            sink: Sink::One(CommonFormat::AVPacket),
-            srcs: Srcs::One(CommonFormat::Text),
+            sinks: vec![PadTemplate::always("sink", caps::av_packet("video"))],
+            srcs: vec![PadTemplate::always("src", caps::video_raw())],
         }
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
-
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                Datagram::Data(data) => match data {
-                    Data::AVPacket(packet) => {
-                        if !self.run_loop(packet) {
-                            break;
-                        }
-                    }
-                    _ => {
-                        error!("Received invalid data type");
-                        break;
-                    }
-                },
-            }
-
-            // while let Some(_msg) = self.sink.try_recv_msg()? {
-            //     // TODO: Handle messages
-            // }
+    fn query_caps(&self, direction: PadDirection, pad: &str) -> Caps {
+        match (direction, pad) {
+            // Only packets of the codec the decoder was opened with can be decoded
+            (PadDirection::Sink, "sink") => match self.codec_id {
+                Some(codec_id) => caps::av_packet("video").intersect(&Caps::new(
+                    Structure::new(caps::AV_PACKET).field("codec-id", codec_id),
+                )),
+                None => caps::av_packet("video"),
+            },
+            (PadDirection::Src, "src") => self.src_caps.clone(),
+            _ => Caps::empty(),
         }
-
-        self.parent.send_finished()
     }
 
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
-    }
+    fn set_caps(&mut self, direction: PadDirection, pad: &str, caps: &Caps) -> Result<(), Error> {
+        if (direction, pad) == (PadDirection::Sink, "sink") {
+            self.time_base = caps
+                .structure()
+                .and_then(|structure| structure.get_fraction("time-base"));
+            if self.decoder.is_none() {
+                self.open(caps)?;
+            }
+        }
 
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
+        Ok(())
+    }
 
-        self.sink.join_thread()
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_transform(self, ctx)
     }
 }
 
 element_def! {
     VideoDecoder,
-    "videodecoder"
+    "videodecoder",
+    "Codec/Decoder/Video",
+    "Decodes compressed video packets"
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "element-demuxsrc", feature = "element-testsink"))]
 mod tests {
     use super::*;
+    use crate::elements::av::{test_util, ResourceLocation};
+    use crate::pipeline::Datagram;
+    use libav::demuxing::Demuxer;
+
+    #[test]
+    fn test_decode() {
+        let path = test_util::write_y4m("streamcraft_test_videodecoder.y4m", 3);
+        let stream = Demuxer::new(ResourceLocation::new_file(path.clone()))
+            .unwrap()
+            .get_video_stream()
+            .unwrap();
+        let videodecoder = VideoDecoder::new(stream).unwrap();
+        let format = VideoFormat {
+            pixel_format: "yuv420p".to_string(),
+            width: 4,
+            height: 2,
+        };
+        assert_eq!(
+            videodecoder.query_caps(PadDirection::Src, "src"),
+            format.caps()
+        );
+
+        let frames = test_util::decode(path, "video", videodecoder, caps::video_raw())
+            .into_iter()
+            .filter_map(|datagram| match datagram {
+                Datagram::Data(Data::VideoFrame(frame)) => Some(frame),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), 3);
+        for (i, frame) in frames.into_iter().enumerate() {
+            assert_eq!(frame.format, format);
+            assert_eq!(frame.planes[0].data[..4], [i as u8; 4]);
+        }
+    }
+
+    // rawvideo holds no frames back, so this only checks that draining at the EOS loses
+    // nothing and that what it returns comes before the EOS
+    #[test]
+    fn test_drain() {
+        let path = test_util::write_y4m("streamcraft_test_videodecoder_drain.y4m", 5);
+        let received = test_util::decode(path, "video", VideoDecoder::default(), caps::video_raw());
+
+        let Some((Datagram::Event(Event::Eos), received)) = received.split_last() else {
+            panic!("Expected the EOS last, got {received:?}");
+        };
+        let pts = received
+            .iter()
+            .filter_map(|datagram| match datagram {
+                Datagram::Data(Data::VideoFrame(frame)) => frame.pts,
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pts,
+            (0..5)
+                .map(|i| Duration::from_millis(40 * i))
+                .collect::<Vec<_>>()
+        );
+    }
 }
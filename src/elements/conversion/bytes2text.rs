@@ -13,17 +13,15 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
-// TODO: Write tests for this!!!!
-
 use crate::{
-    element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    caps, element_def,
+    element_traits::{
+        base::{self, Transform},
+        Element, ElementArchitecture, PadTemplate,
+    },
+    pipeline::{error::Error, event::Event, Data, ElementContext},
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-
 ///```text
 ///            +---------------------------+
 ///            |______                _____|
@@ -31,9 +29,11 @@ use crossbeam_channel::{bounded, unbounded, Receiver};
 ///            |^^^^^^                ^^^^^|
 ///            +---------------------------+
 ///```
+///
+/// A UTF-8 sequence that is split between two chunks of bytes is held back until the rest of it
+/// arrives. If the stream ends in the middle of one, it is sent with the replacement character.
 pub struct Bytes2Text {
-    sink: SinkPipe,
-    parent: Parent,
+    partial: Vec<u8>,
 }
 
 impl Default for Bytes2Text {
@@ -45,133 +45,163 @@ impl Default for Bytes2Text {
 impl Bytes2Text {
     pub fn new() -> Self {
         Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
+            partial: Vec::new(),
         }
     }
+}
 
-    /// Link the sink element.
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::TextSink {
-            return Err(Error::InvalidSinkType);
-        }
+impl Transform for Bytes2Text {
+    fn transform(&mut self, data: Data) -> Result<Vec<Data>, Error> {
+        let Data::Bytes(bytes) = data else {
+            return Err(Error::ReceivedInvalidDatagramFromParent);
+        };
 
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::Text {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
+        self.partial.extend(bytes);
+        let valid_up_to = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            // The rest is the start of a sequence that is continued in the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(Error::InvalidData(format!("String conversion failed: {e}"))),
+        };
+        if valid_up_to == 0 {
+            return Ok(Vec::new());
         }
 
-        Err(Error::InvalidSinkType)
+        let rest = self.partial.split_off(valid_up_to);
+        let bytes = std::mem::replace(&mut self.partial, rest);
+        // Only contains valid UTF-8 as checked above
+        Ok(vec![Data::Text(
+            String::from_utf8_lossy(&bytes).into_owned(),
+        )])
     }
 
-    fn init(&mut self) -> Result<(), Error> {
-        let (datagram_sender, datagram_receiver) = bounded(0);
-        let (msg_sender, my_msg_receiver) = unbounded();
-        let parent = Parent::new(msg_sender);
-        let mut sink_element = self.sink.take_element()?;
-        sink_element.set_parent(parent);
-        let datagram_receiver_clone = datagram_receiver.clone();
-
-        self.sink.thread_handle = Some(std::thread::spawn(move || {
-            match sink_element.run(datagram_receiver_clone) {
-                Ok(_) => {}
-                Err(e) => error!("{e}"),
+    fn sink_event(&mut self, event: &Event) -> Result<Vec<Data>, Error> {
+        match event {
+            Event::Eos if !self.partial.is_empty() => {
+                let text = String::from_utf8_lossy(&self.partial).into_owned();
+                self.partial.clear();
+                return Ok(vec![Data::Text(text)]);
             }
-        }));
-        self.sink.msg_receiver = Some(my_msg_receiver);
-        self.sink.datagram_sender = Some(datagram_sender);
-
-        Ok(())
-    }
-
-    fn run_loop(&mut self, buf: Vec<u8>) -> bool {
-        let text = match String::from_utf8(buf) {
-            Ok(t) => Data::Text(t),
-            Err(e) => {
-                error!("String conversion failed: {e}");
-                return false;
-            }
-        };
-
-        if let Err(e) = self.sink.send_datagram(Datagram::Data(text)) {
-            error!("{}", e);
-            return false;
+            Event::FlushStart | Event::StreamStart => self.partial.clear(),
+            _ => {}
         }
 
-        true
+        Ok(Vec::new())
     }
 }
 
 impl Element for Bytes2Text {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::BytesSink
-    }
-
     fn get_architecture(&self) -> ElementArchitecture {
         ElementArchitecture {
-            sink: Sink::One(CommonFormat::Bytes),
-            srcs: Srcs::One(CommonFormat::Text),
+            sinks: vec![PadTemplate::always("sink", caps::bytes())],
+            srcs: vec![PadTemplate::always("src", caps::text_utf8())],
         }
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
-
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                Datagram::Data(data) => match data {
-                    Data::Bytes(bytes) => {
-                        if !self.run_loop(bytes) {
-                            break;
-                        }
-                    }
-                    _ => {
-                        error!("Received invalid data type");
-                        break;
-                    }
-                },
-            }
-
-            while let Some(_msg) = self.sink.try_recv_msg()? {
-                // TODO: Handle messages
-            }
-        }
-
-        self.parent.send_finished()
-    }
-
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
-    }
-
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
-
-        self.sink.join_thread()
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_transform(self, ctx)
     }
 }
 
 element_def! {
     Bytes2Text,
-    "bytes2text"
+    "bytes2text",
+    "Converter/Text",
+    "Converts UTF-8 bytes to text"
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        elements::misc::{testsink::TestSink, testsrc::TestSrc},
+        pipeline::{graph::PipelineGraph, Datagram, Pipeline},
+    };
+
     use super::*;
 
     #[test]
     fn test_basic() {
-        // TODO
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = Arc::clone(&received);
+        let testsink = TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, data| {
+                received_clone.lock().unwrap().push(data);
+                true
+            },
+        );
+        let testsrc = TestSrc::new(
+            caps::bytes(),
+            vec![
+                Datagram::Data(Data::Bytes(b"Hello, ".to_vec())),
+                Datagram::Data(Data::Bytes(b"World!".to_vec())),
+            ],
+        );
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let bytes2text = graph.add_element(Bytes2Text::new());
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc, "src"), (bytes2text, "sink")).unwrap();
+        graph.link((bytes2text, "src"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.init().unwrap();
+        while pipeline.iter().is_ok() {}
+        drop(pipeline);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Data::Text(String::from("Hello, ")),
+                Data::Text(String::from("World!")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_sequence() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = Arc::clone(&received);
+        let testsink = TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, data| {
+                received_clone.lock().unwrap().push(data);
+                true
+            },
+        );
+        // "æ" is 0xC3 0xA6
+        let testsrc = TestSrc::new(
+            caps::bytes(),
+            vec![
+                Datagram::Data(Data::Bytes(b"Bl\xC3".to_vec())),
+                Datagram::Data(Data::Bytes(b"\xA6b\xC3".to_vec())),
+            ],
+        );
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let bytes2text = graph.add_element(Bytes2Text::new());
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc, "src"), (bytes2text, "sink")).unwrap();
+        graph.link((bytes2text, "src"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+        drop(pipeline);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Data::Text(String::from("Bl")),
+                Data::Text(String::from("æb")),
+                Data::Text(String::from("\u{FFFD}")),
+            ]
+        );
     }
 }
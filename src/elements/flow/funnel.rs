@@ -0,0 +1,182 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    caps::Caps,
+    element_def,
+    element_traits::{Element, ElementArchitecture, PadDirection, PadPresence, PadTemplate},
+    pipeline::{error::Error, event::Event, Datagram, ElementContext, Input, Message},
+};
+
+/// Merges every sink pad into one src pad, in the order the datagrams arrive. All inputs must
+/// have the same format. Request sink pads by linking `sink_%u`.
+///
+/// [`Event::StreamStart`] is only sent for the first input and [`Event::Eos`] once every input
+/// has ended.
+///
+///```text
+///            +----------------------+
+///            |_______          _____|
+///    ???? -->| sink_0 | Funnel | src |----> ????
+///            |^^^^^^^          ^^^^^|
+///    ???? -->| sink_1 |             |
+///            |^^^^^^^               |
+///            |  ...                 |
+///            +----------------------+
+///```
+pub struct Funnel {
+    caps: Option<Caps>,
+}
+
+impl Default for Funnel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Funnel {
+    pub fn new() -> Self {
+        Self { caps: None }
+    }
+}
+
+impl Element for Funnel {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: vec![PadTemplate::new(
+                "sink_%u",
+                PadPresence::Request,
+                Caps::any(),
+            )],
+            srcs: vec![PadTemplate::always("src", Caps::any())],
+        }
+    }
+
+    fn query_caps(&self, _direction: PadDirection, _pad: &str) -> Caps {
+        // The first input that is negotiated decides the format of the rest
+        self.caps.clone().unwrap_or_else(Caps::any)
+    }
+
+    fn set_caps(&mut self, direction: PadDirection, _pad: &str, caps: &Caps) -> Result<(), Error> {
+        if direction == PadDirection::Sink {
+            self.caps = Some(caps.clone());
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        let mut eos = vec![false; ctx.sink_names().count()];
+        let mut open = eos.len();
+        let mut stream_started = false;
+
+        while open > 0 {
+            match ctx.recv(self)? {
+                Input::Sink(_, Datagram::Message(Message::Quit)) => open -= 1,
+                Input::Sink(_, Datagram::Event(Event::StreamStart)) => {
+                    if !std::mem::replace(&mut stream_started, true) {
+                        ctx.push_event(Event::StreamStart)?;
+                    }
+                }
+                Input::Sink(index, Datagram::Event(Event::Eos)) => {
+                    eos[index] = true;
+                    if eos.iter().all(|eos| *eos) {
+                        ctx.push_event(Event::Eos)?;
+                    }
+                }
+                Input::Sink(index, Datagram::Event(Event::FlushStop)) => {
+                    eos[index] = false;
+                    ctx.push_event(Event::FlushStop)?;
+                }
+                Input::Sink(_, datagram) => ctx.push("src", datagram)?,
+                Input::Control(_) => return Err(Error::ReceivedInvalidDatagramFromParent),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+element_def! {
+    Funnel,
+    "funnel",
+    "Generic",
+    "Merges its inputs into one output"
+}
+
+#[cfg(all(test, feature = "element-testsrc", feature = "element-testsink"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        caps,
+        elements::{
+            flow::test_util::{collecting_sink, text},
+            misc::testsrc::TestSrc,
+        },
+        pipeline::{event::Segment, graph::PipelineGraph, Data, Pipeline},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_basic() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        let (mut testsink, received) = collecting_sink();
+        testsink.set_on_event(move |event| events_clone.lock().unwrap().push(event));
+
+        let mut graph = PipelineGraph::new();
+        let testsrc_a = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![text("a1"), text("a2")],
+        ));
+        let testsrc_b = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![text("b1"), text("b2")],
+        ));
+        let funnel = graph.add_element(Funnel::new());
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc_a, "src"), (funnel, "sink_%u")).unwrap();
+        graph.link((testsrc_b, "src"), (funnel, "sink_%u")).unwrap();
+        graph.link((funnel, "src"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+        drop(pipeline);
+
+        let received = received.lock().unwrap();
+        let position = |text: &str| {
+            received
+                .iter()
+                .position(|data| *data == Data::Text(text.to_string()))
+                .unwrap()
+        };
+        assert_eq!(received.len(), 4);
+        assert!(position("a1") < position("a2"));
+        assert!(position("b1") < position("b2"));
+        // Every input starts with a stream start and a segment
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                Event::StreamStart,
+                Event::Segment(Segment::default()),
+                Event::Segment(Segment::default()),
+                Event::Eos
+            ]
+        );
+    }
+}
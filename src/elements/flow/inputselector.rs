@@ -0,0 +1,332 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    caps::Caps,
+    debug, element_def,
+    element_traits::{
+        compare_pad_names,
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadDirection, PadPresence, PadTemplate,
+    },
+    pipeline::{
+        error::Error, event::Event, state::State, Datagram, ElementContext, Input, Message,
+    },
+};
+
+/// What the selector remembers about an input so it can be switched to.
+#[derive(Default)]
+struct InputState {
+    segment: Option<Event>,
+    eos: bool,
+}
+
+/// Forwards the active sink pad and drops everything from the other ones. Request sink pads by
+/// linking `sink_%u`. The first pad by number is active until another one is selected with the
+/// `active-pad` property, which can be changed while the pipeline is running, see
+/// [`Pipeline::set_property`](crate::pipeline::Pipeline::set_property).
+///
+/// When switching, the last segment of the new input is sent, and EOS if it already ended.
+///
+///```text
+///            +-----------------------------+
+///            |_______                 _____|
+///    ???? -->| sink_0 | InputSelector | src |----> ????
+///            |^^^^^^^                 ^^^^^|
+///    ???? -->| sink_1 |                    |
+///            |^^^^^^^                      |
+///            |  ...                        |
+///            +-----------------------------+
+///```
+pub struct InputSelector {
+    caps: Option<Caps>,
+    /// `None` for the first pad.
+    active_pad: Option<String>,
+}
+
+impl Default for InputSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSelector {
+    pub fn new() -> Self {
+        Self {
+            caps: None,
+            active_pad: None,
+        }
+    }
+
+    /// Forward the sink pad named `pad` from the next datagram on.
+    pub fn set_active_pad(&mut self, pad: &str) {
+        self.active_pad = Some(pad.to_string());
+    }
+
+    /// Index of the active sink pad, `None` if the selected pad does not exist.
+    fn active_index(&self, ctx: &ElementContext) -> Option<usize> {
+        match &self.active_pad {
+            Some(pad) => ctx.sink_names().position(|name| name == pad),
+            None => ctx
+                .sink_names()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| compare_pad_names(a, b))
+                .map(|(index, _)| index),
+        }
+    }
+}
+
+impl Element for InputSelector {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: vec![PadTemplate::new(
+                "sink_%u",
+                PadPresence::Request,
+                Caps::any(),
+            )],
+            srcs: vec![PadTemplate::always("src", Caps::any())],
+        }
+    }
+
+    fn properties(&self) -> Vec<PropertySpec> {
+        vec![PropertySpec::string(
+            "active-pad",
+            "Name of the sink pad to forward, empty for the first one",
+            "",
+        )
+        .mutable_in(State::Playing)]
+    }
+
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        match name {
+            "active-pad" => Some(PropertyValue::Str(
+                self.active_pad.clone().unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value) {
+            ("active-pad", PropertyValue::Str(pad)) if pad.is_empty() => self.active_pad = None,
+            ("active-pad", PropertyValue::Str(pad)) => self.set_active_pad(&pad),
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn query_forward_pad(&self, direction: PadDirection, _pad: &str) -> Option<String> {
+        // Only the active input is seen downstream
+        match direction {
+            PadDirection::Src => self.active_pad.clone(),
+            PadDirection::Sink => None,
+        }
+    }
+
+    fn query_caps(&self, _direction: PadDirection, _pad: &str) -> Caps {
+        // Every input must fit the same output
+        self.caps.clone().unwrap_or_else(Caps::any)
+    }
+
+    fn set_caps(&mut self, direction: PadDirection, _pad: &str, caps: &Caps) -> Result<(), Error> {
+        if direction == PadDirection::Sink {
+            self.caps = Some(caps.clone());
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        let mut inputs = ctx
+            .sink_names()
+            .map(|_| InputState::default())
+            .collect::<Vec<InputState>>();
+        let mut open = inputs.len();
+        let mut active = self.active_index(ctx);
+
+        while open > 0 {
+            let (index, datagram) = match ctx.recv(self)? {
+                Input::Sink(index, datagram) => (index, datagram),
+                Input::Control(_) => return Err(Error::ReceivedInvalidDatagramFromParent),
+            };
+
+            let selected = self.active_index(ctx);
+            if selected != active {
+                active = selected;
+                debug!("Switched to input {active:?}");
+                if let Some(input) = active.and_then(|index| inputs.get(index)) {
+                    if let Some(segment) = &input.segment {
+                        ctx.push_event(segment.clone())?;
+                    }
+                    if input.eos {
+                        ctx.push_event(Event::Eos)?;
+                    }
+                }
+            }
+
+            let input = &mut inputs[index];
+            match &datagram {
+                Datagram::Message(Message::Quit) => {
+                    open -= 1;
+                    continue;
+                }
+                Datagram::Event(event @ Event::Segment(_)) => input.segment = Some(event.clone()),
+                Datagram::Event(Event::Eos) => input.eos = true,
+                Datagram::Event(Event::FlushStop) => input.eos = false,
+                _ => {}
+            }
+
+            if active == Some(index) {
+                ctx.push("src", datagram)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+element_def! {
+    InputSelector,
+    "inputselector",
+    "Generic",
+    "Forwards one of its inputs"
+}
+
+#[cfg(all(test, feature = "element-testsrc", feature = "element-testsink"))]
+mod tests {
+    use crate::{
+        caps,
+        elements::{
+            flow::test_util::{collecting_sink, text},
+            misc::testsrc::TestSrc,
+        },
+        pipeline::{graph::PipelineGraph, Data, Pipeline},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_active_pad() {
+        let (testsink, received) = collecting_sink();
+        let mut inputselector = InputSelector::new();
+        inputselector.set_active_pad("sink_1");
+
+        let mut graph = PipelineGraph::new();
+        let testsrc_a = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![text("a1"), text("a2")],
+        ));
+        let testsrc_b = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![text("b1"), text("b2")],
+        ));
+        let inputselector = graph.add_element(inputselector);
+        let testsink = graph.add_element(testsink);
+        graph
+            .link((testsrc_a, "src"), (inputselector, "sink_%u"))
+            .unwrap();
+        graph
+            .link((testsrc_b, "src"), (inputselector, "sink_%u"))
+            .unwrap();
+        graph
+            .link((inputselector, "src"), (testsink, "sink"))
+            .unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+        drop(pipeline);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Data::Text(String::from("b1")),
+                Data::Text(String::from("b2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_pad_by_name() {
+        let (testsink, received) = collecting_sink();
+
+        let mut graph = PipelineGraph::new();
+        let testsrc_a = graph.add_element(TestSrc::new(caps::text_utf8(), vec![text("a")]));
+        let testsrc_b = graph.add_element(TestSrc::new(caps::text_utf8(), vec![text("b")]));
+        let inputselector = graph.add_element(InputSelector::new());
+        let testsink = graph.add_element(testsink);
+        // Linked out of order
+        graph
+            .link((testsrc_b, "src"), (inputselector, "sink_1"))
+            .unwrap();
+        graph
+            .link((testsrc_a, "src"), (inputselector, "sink_0"))
+            .unwrap();
+        graph
+            .link((inputselector, "src"), (testsink, "sink"))
+            .unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+        drop(pipeline);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![Data::Text(String::from("a"))]
+        );
+    }
+
+    #[cfg(feature = "element-filesrc")]
+    #[test]
+    fn test_query_active_pad() {
+        use crate::{
+            elements::{io::filesrc::FileSrc, misc::testsink::TestSink},
+            pipeline::query::Format,
+        };
+
+        let mut graph = PipelineGraph::new();
+        let readme = graph.add_element(FileSrc::new(std::fs::File::open("README.md").unwrap()));
+        let manifest = graph.add_element(FileSrc::new(std::fs::File::open("Cargo.toml").unwrap()));
+        let inputselector = graph.add_element(InputSelector::new());
+        let testsink = graph.add_element(TestSink::default());
+        graph
+            .link((readme, "src"), (inputselector, "sink_%u"))
+            .unwrap();
+        graph
+            .link((manifest, "src"), (inputselector, "sink_%u"))
+            .unwrap();
+        graph
+            .link((inputselector, "src"), (testsink, "sink"))
+            .unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.set_state(State::Paused).unwrap();
+        // Answered by the source of the active pad
+        assert_eq!(
+            pipeline.query_duration(Format::Bytes).unwrap(),
+            Some(std::fs::metadata("README.md").unwrap().len())
+        );
+        pipeline
+            .set_property(inputselector, "active-pad", "sink_1")
+            .unwrap();
+        assert_eq!(
+            pipeline.query_duration(Format::Bytes).unwrap(),
+            Some(std::fs::metadata("Cargo.toml").unwrap().len())
+        );
+        pipeline.set_state(State::Null).unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+#[cfg(feature = "element-funnel")]
+pub mod funnel;
+#[cfg(feature = "element-inputselector")]
+pub mod inputselector;
+#[cfg(feature = "element-queue")]
+pub mod queue;
+#[cfg(feature = "element-tee")]
+pub mod tee;
+
+#[cfg(all(test, feature = "element-testsrc", feature = "element-testsink"))]
+mod test_util;
@@ -0,0 +1,605 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    caps::Caps,
+    debug, element_def,
+    element_traits::{
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadDirection, PadTemplate,
+    },
+    error,
+    pipeline::{
+        error::Error, event::Event, state::State, Data, Datagram, ElementContext, Input, Message,
+        SinkPipe,
+    },
+};
+
+/// How long to wait for room at a time before handling queries and events again.
+const WAIT_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// What to do with new data when the queue is full.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Leaky {
+    /// Wait until there is room.
+    #[default]
+    No,
+    /// Drop the new data.
+    Upstream,
+    /// Drop the oldest data in the queue.
+    Downstream,
+}
+
+/// How full a [`Queue`] is and how much it has dropped.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct QueueStats {
+    pub buffers: usize,
+    pub bytes: usize,
+    /// Time between the oldest and newest data with a timestamp.
+    pub time: Duration,
+    pub dropped: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    max_buffers: usize,
+    max_bytes: usize,
+    max_time: Duration,
+    leaky: Leaky,
+}
+
+struct Item {
+    datagram: Datagram,
+    size: usize,
+    time: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Level {
+    items: VecDeque<Item>,
+    stats: QueueStats,
+    /// Nothing more will be added, the queue is emptied and then the src side stops.
+    closed: bool,
+}
+
+impl Level {
+    fn push(&mut self, item: Item) {
+        if item.is_data() {
+            self.stats.buffers += 1;
+            self.stats.bytes += item.size;
+        }
+        self.items.push_back(item);
+        self.update_time();
+    }
+
+    fn remove(&mut self, index: usize) -> Option<Item> {
+        let item = self.items.remove(index)?;
+        if item.is_data() {
+            self.stats.buffers -= 1;
+            self.stats.bytes -= item.size;
+        }
+        self.update_time();
+        Some(item)
+    }
+
+    fn clear_data(&mut self) {
+        self.items.retain(|item| !item.is_data());
+        self.stats.buffers = 0;
+        self.stats.bytes = 0;
+        self.stats.time = Duration::ZERO;
+    }
+
+    fn update_time(&mut self) {
+        let oldest = self.items.iter().find_map(|item| item.time);
+        let newest = self.items.iter().rev().find_map(|item| item.time);
+        self.stats.time = match (oldest, newest) {
+            (Some(oldest), Some(newest)) => newest.saturating_sub(oldest),
+            _ => Duration::ZERO,
+        };
+    }
+
+    fn is_full(&self, limits: &Limits) -> bool {
+        (limits.max_buffers > 0 && self.stats.buffers >= limits.max_buffers)
+            || (limits.max_bytes > 0 && self.stats.bytes >= limits.max_bytes)
+            || (!limits.max_time.is_zero() && self.stats.time >= limits.max_time)
+    }
+}
+
+impl Item {
+    fn is_data(&self) -> bool {
+        matches!(self.datagram, Datagram::Data(_))
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    level: Mutex<Level>,
+    changed: Condvar,
+}
+
+/// Get the statistics of a [`Queue`] that is running in a pipeline.
+#[derive(Clone)]
+pub struct QueueStatsHandle {
+    shared: Arc<Shared>,
+}
+
+impl QueueStatsHandle {
+    pub fn get(&self) -> QueueStats {
+        self.shared
+            .level
+            .lock()
+            .map(|level| level.stats)
+            .unwrap_or_default()
+    }
+}
+
+/// Buffers data between the element before and after it, which run independently of each other
+/// until the queue is full. Limits that are 0 are disabled.
+///
+///```text
+///            +----------------------+
+///            |______           _____|
+///    ???? -->| sink |  Queue  | src |----> ????
+///            |^^^^^^           ^^^^^|
+///            +----------------------+
+///```
+pub struct Queue {
+    limits: Limits,
+    caps: Option<Caps>,
+    time_base: Option<(i64, i64)>,
+    shared: Arc<Shared>,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Self {
+            limits: Limits {
+                max_buffers: 200,
+                max_bytes: 10 * 1024 * 1024,
+                max_time: Duration::from_secs(1),
+                leaky: Leaky::No,
+            },
+            caps: None,
+            time_base: None,
+            shared: Arc::default(),
+        }
+    }
+
+    pub fn set_max_buffers(&mut self, max_buffers: usize) {
+        self.limits.max_buffers = max_buffers;
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.limits.max_bytes = max_bytes;
+    }
+
+    pub fn set_max_time(&mut self, max_time: Duration) {
+        self.limits.max_time = max_time;
+    }
+
+    pub fn set_leaky(&mut self, leaky: Leaky) {
+        self.limits.leaky = leaky;
+    }
+
+    /// Get a handle to the statistics that can be used after the queue is added to a pipeline.
+    pub fn stats_handle(&self) -> QueueStatsHandle {
+        QueueStatsHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    fn item(&self, datagram: Datagram) -> Item {
+        let (size, time) = match &datagram {
+            Datagram::Data(data @ Data::AVPacket(packet)) => {
+                let time = self
+                    .time_base
+                    .zip(packet.pts())
+                    .and_then(|((num, den), pts)| {
+                        (pts >= 0 && num > 0 && den > 0).then(|| {
+                            Duration::from_nanos(
+                                (pts as u128 * num as u128 * 1_000_000_000 / den as u128) as u64,
+                            )
+                        })
+                    });
+                (data.size(), time)
+            }
+            Datagram::Data(data @ Data::VideoFrame(frame)) => (data.size(), frame.pts),
+            Datagram::Data(data @ Data::AudioBuffer(buffer)) => (data.size(), buffer.pts),
+            Datagram::Data(data) => (data.size(), None),
+            _ => (0, None),
+        };
+
+        Item {
+            datagram,
+            size,
+            time,
+        }
+    }
+
+    /// Add an item to the queue. If the queue is full and not leaky, wait up to `timeout` for
+    /// room and give the item back if there is none.
+    fn enqueue(&self, item: Item, timeout: Duration) -> Result<Option<Item>, Error> {
+        let mut level = self.shared.level.lock().map_err(|_| Error::QueuePoisoned)?;
+
+        if let Datagram::Event(Event::FlushStart) = item.datagram {
+            level.clear_data();
+        }
+
+        // Events are never dropped and do not wait for room
+        while item.is_data() && level.is_full(&self.limits) {
+            match self.limits.leaky {
+                Leaky::No => {
+                    let (new_level, wait) = self
+                        .shared
+                        .changed
+                        .wait_timeout(level, timeout)
+                        .map_err(|_| Error::QueuePoisoned)?;
+                    level = new_level;
+                    if wait.timed_out() && level.is_full(&self.limits) {
+                        return Ok(Some(item));
+                    }
+                }
+                Leaky::Upstream => {
+                    level.stats.dropped += 1;
+                    return Ok(None);
+                }
+                Leaky::Downstream => match level.items.iter().position(Item::is_data) {
+                    Some(oldest) => {
+                        level.remove(oldest);
+                        level.stats.dropped += 1;
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        level.push(item);
+        self.shared.changed.notify_all();
+
+        Ok(None)
+    }
+
+    /// Add a datagram to the queue. While waiting for room, queries and upstream events are
+    /// still handled so that downstream can not dead lock on them.
+    fn enqueue_datagram(
+        &mut self,
+        ctx: &mut ElementContext,
+        datagram: Datagram,
+    ) -> Result<(), Error> {
+        let mut item = self.item(datagram);
+        while let Some(waiting) = self.enqueue(item, WAIT_TIMEOUT)? {
+            ctx.handle_pending_requests(self);
+            item = waiting;
+        }
+
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Ok(mut level) = self.shared.level.lock() {
+            level.closed = true;
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// Push datagrams downstream until the queue is closed and empty.
+    fn push_loop(shared: Arc<Shared>, src: SinkPipe) -> Result<(), Error> {
+        let res = loop {
+            let item = {
+                let Ok(mut level) = shared.level.lock() else {
+                    break Err(Error::QueuePoisoned);
+                };
+                while level.items.is_empty() && !level.closed {
+                    level = match shared.changed.wait(level) {
+                        Ok(level) => level,
+                        Err(_) => return Err(Error::QueuePoisoned),
+                    };
+                }
+                match level.remove(0) {
+                    Some(item) => item,
+                    None => break Ok(()),
+                }
+            };
+            shared.changed.notify_all();
+
+            if src.is_linked() {
+                if let Err(e) = src.send_datagram(item.datagram) {
+                    debug!("Downstream is gone");
+                    break Err(e);
+                }
+            }
+        };
+
+        // Nobody is taking data any more, so upstream must not wait for room
+        if let Ok(mut level) = shared.level.lock() {
+            level.closed = true;
+            level.clear_data();
+        }
+        shared.changed.notify_all();
+
+        res
+    }
+}
+
+impl Leaky {
+    fn nick(&self) -> &'static str {
+        match self {
+            Leaky::No => "no",
+            Leaky::Upstream => "upstream",
+            Leaky::Downstream => "downstream",
+        }
+    }
+}
+
+impl Element for Queue {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: vec![PadTemplate::always("sink", Caps::any())],
+            srcs: vec![PadTemplate::always("src", Caps::any())],
+        }
+    }
+
+    fn properties(&self) -> Vec<PropertySpec> {
+        let defaults = Queue::new().limits;
+        vec![
+            PropertySpec::int(
+                "max-buffers",
+                "Maximum number of buffers in the queue, 0 for no limit",
+                0,
+                i64::MAX,
+                defaults.max_buffers as i64,
+            )
+            .mutable_in(State::Playing),
+            PropertySpec::int(
+                "max-bytes",
+                "Maximum number of bytes in the queue, 0 for no limit",
+                0,
+                i64::MAX,
+                defaults.max_bytes as i64,
+            )
+            .mutable_in(State::Playing),
+            PropertySpec::int(
+                "max-time",
+                "Maximum nanoseconds of data in the queue, 0 for no limit",
+                0,
+                i64::MAX,
+                defaults.max_time.as_nanos() as i64,
+            )
+            .mutable_in(State::Playing),
+            PropertySpec::enumeration(
+                "leaky",
+                "What to drop when the queue is full",
+                &["no", "upstream", "downstream"],
+                defaults.leaky.nick(),
+            )
+            .mutable_in(State::Playing),
+            PropertySpec::int(
+                "current-buffers",
+                "Number of buffers in the queue",
+                0,
+                i64::MAX,
+                0,
+            )
+            .read_only(),
+            PropertySpec::int(
+                "current-bytes",
+                "Number of bytes in the queue",
+                0,
+                i64::MAX,
+                0,
+            )
+            .read_only(),
+            PropertySpec::int(
+                "current-time",
+                "Nanoseconds of data in the queue",
+                0,
+                i64::MAX,
+                0,
+            )
+            .read_only(),
+            PropertySpec::int(
+                "dropped",
+                "Number of buffers dropped because the queue was full",
+                0,
+                i64::MAX,
+                0,
+            )
+            .read_only(),
+        ]
+    }
+
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        let stats = self.stats_handle().get();
+        let value = match name {
+            "max-buffers" => self.limits.max_buffers as i64,
+            "max-bytes" => self.limits.max_bytes as i64,
+            "max-time" => self.limits.max_time.as_nanos() as i64,
+            "leaky" => return Some(PropertyValue::Enum(self.limits.leaky.nick().to_string())),
+            "current-buffers" => stats.buffers as i64,
+            "current-bytes" => stats.bytes as i64,
+            "current-time" => stats.time.as_nanos() as i64,
+            "dropped" => stats.dropped as i64,
+            _ => return None,
+        };
+
+        Some(PropertyValue::Int(value))
+    }
+
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value) {
+            ("max-buffers", PropertyValue::Int(n)) => self.set_max_buffers(n as usize),
+            ("max-bytes", PropertyValue::Int(n)) => self.set_max_bytes(n as usize),
+            ("max-time", PropertyValue::Int(n)) => {
+                self.set_max_time(Duration::from_nanos(n as u64))
+            }
+            ("leaky", PropertyValue::Enum(leaky)) => self.set_leaky(match leaky.as_str() {
+                "upstream" => Leaky::Upstream,
+                "downstream" => Leaky::Downstream,
+                _ => Leaky::No,
+            }),
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
+        }
+        // Upstream may be waiting for room that the new limits make
+        self.shared.changed.notify_all();
+
+        Ok(())
+    }
+
+    fn query_caps(&self, direction: PadDirection, _pad: &str) -> Caps {
+        match (direction, &self.caps) {
+            // Whatever came in goes out
+            (PadDirection::Src, Some(caps)) => caps.clone(),
+            _ => Caps::any(),
+        }
+    }
+
+    fn set_caps(&mut self, direction: PadDirection, _pad: &str, caps: &Caps) -> Result<(), Error> {
+        if direction == PadDirection::Sink {
+            self.time_base = caps
+                .structure()
+                .and_then(|structure| structure.get_fraction("time-base"));
+            self.caps = Some(caps.clone());
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        if let Ok(mut level) = self.shared.level.lock() {
+            *level = Level::default();
+        }
+        let src = SinkPipe {
+            datagram_sender: ctx.src("src")?.datagram_sender.clone(),
+        };
+        let shared = Arc::clone(&self.shared);
+        let push_thread = std::thread::spawn(move || Queue::push_loop(shared, src));
+
+        let res = loop {
+            match ctx.recv(self) {
+                Ok(Input::Sink(_, Datagram::Message(Message::Quit))) => break Ok(()),
+                Ok(Input::Sink(_, datagram)) => {
+                    if let Err(e) = self.enqueue_datagram(ctx, datagram) {
+                        break Err(e);
+                    }
+                    if self.shared.level.lock().is_ok_and(|level| level.closed) {
+                        break Ok(());
+                    }
+                }
+                Ok(Input::Control(_)) => break Err(Error::ReceivedInvalidDatagramFromParent),
+                Err(e) => break Err(e),
+            }
+        };
+
+        // Let the push thread empty the queue before the src pad is closed
+        self.close();
+        let pushed = match push_thread.join() {
+            Ok(pushed) => pushed.map_err(|e| ctx.pad_error("src", e)),
+            Err(_) => {
+                error!("{}", Error::FailedToJoinThread);
+                Err(Error::FailedToJoinThread)
+            }
+        };
+
+        res.and(pushed)
+    }
+}
+
+element_def! {
+    Queue,
+    "queue",
+    "Generic",
+    "Buffers data so the elements around it run independently"
+}
+
+#[cfg(all(test, feature = "element-testsrc", feature = "element-testsink"))]
+mod tests {
+    use crate::elements::flow::test_util::{run_through, text};
+
+    use super::*;
+
+    #[test]
+    fn test_leaky_downstream() {
+        let mut queue = Queue::new();
+        queue.set_max_buffers(2);
+        queue.set_leaky(Leaky::Downstream);
+        let stats = queue.stats_handle();
+
+        // Nothing is running, so nothing leaves the queue
+        for datagram in [text("a"), text("b"), Datagram::Event(Event::Eos), text("c")] {
+            let item = queue.item(datagram);
+            assert!(queue.enqueue(item, Duration::ZERO).unwrap().is_none());
+        }
+
+        let level = queue.shared.level.lock().unwrap();
+        let items = level
+            .items
+            .iter()
+            .map(|item| item.datagram.try_clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![text("b"), Datagram::Event(Event::Eos), text("c")]
+        );
+        drop(level);
+        assert_eq!(
+            stats.get(),
+            QueueStats {
+                buffers: 2,
+                bytes: 2,
+                time: Duration::ZERO,
+                dropped: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_full() {
+        let mut queue = Queue::new();
+        queue.set_max_buffers(1);
+
+        assert!(queue
+            .enqueue(queue.item(text("a")), Duration::ZERO)
+            .unwrap()
+            .is_none());
+        // Given back so the element can handle requests before waiting again
+        let item = queue
+            .enqueue(queue.item(text("b")), Duration::from_millis(1))
+            .unwrap();
+        assert_eq!(item.map(|item| item.datagram), Some(text("b")));
+    }
+
+    #[test]
+    fn test_basic() {
+        assert_eq!(
+            run_through(Queue::new(), vec![text("Hello"), text("World")]),
+            vec![
+                Data::Text(String::from("Hello")),
+                Data::Text(String::from("World"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    caps::Caps,
+    debug, element_def,
+    element_traits::{
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadDirection, PadPresence, PadTemplate,
+    },
+    pipeline::{error::Error, state::State, Datagram, ElementContext, Input, Message},
+};
+
+/// What to do with data for a branch that is not ready to receive it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum BlockedBranch {
+    /// Wait for the branch, which stalls the other branches.
+    #[default]
+    Wait,
+    /// Drop the data for that branch. Events are still waited for.
+    ///
+    /// A branch is only ready while there is room on its link, and a link made with
+    /// [`PipelineGraph::link`](crate::pipeline::graph::PipelineGraph::link) has none, so data
+    /// is dropped whenever the element after it is busy. Link the branches with
+    /// [`PipelineGraph::link_with_capacity`](crate::pipeline::graph::PipelineGraph::link_with_capacity),
+    /// or start each branch with a leaky [`Queue`](super::queue::Queue).
+    Drop,
+}
+
+/// What to do when a branch can not receive data any more, e.g. because its element stopped.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum FailedBranch {
+    /// Stop sending to the branch and continue with the rest. The tee only fails when every
+    /// branch has failed.
+    #[default]
+    Remove,
+    /// Fail the tee, which stops all branches.
+    Fail,
+}
+
+/// Sends everything it receives to every src pad. Request src pads by linking `src_%u`.
+///
+///```text
+///            +-------------------+
+///            |______      _______|
+///    ???? -->| sink | Tee | src_0 |----> ????
+///            |^^^^^^      _______|
+///            |           | src_1 |----> ????
+///            |            ^^^^^^^|
+///            |              ...  |
+///            +-------------------+
+///```
+pub struct Tee {
+    caps: Option<Caps>,
+    blocked_branch: BlockedBranch,
+    failed_branch: FailedBranch,
+}
+
+impl Default for Tee {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tee {
+    pub fn new() -> Self {
+        Self {
+            caps: None,
+            blocked_branch: BlockedBranch::Wait,
+            failed_branch: FailedBranch::Remove,
+        }
+    }
+
+    pub fn set_blocked_branch(&mut self, blocked_branch: BlockedBranch) {
+        self.blocked_branch = blocked_branch;
+    }
+
+    pub fn set_failed_branch(&mut self, failed_branch: FailedBranch) {
+        self.failed_branch = failed_branch;
+    }
+
+    /// Send `datagram` to every branch that has not failed, adding the ones that fail now.
+    fn push(
+        &self,
+        ctx: &ElementContext,
+        branches: &[String],
+        failed: &mut Vec<String>,
+        datagram: Datagram,
+    ) -> Result<(), Error> {
+        let mut last_error = None;
+        for name in branches {
+            if failed.contains(name) {
+                continue;
+            }
+            let src = ctx.src(name)?;
+            let copy = datagram.try_clone()?;
+            let res = match (&datagram, self.blocked_branch) {
+                (Datagram::Data(_), BlockedBranch::Drop) => {
+                    src.try_send_datagram(copy).map(|sent| {
+                        if !sent {
+                            debug!("Branch {name} is blocked, dropping data");
+                        }
+                    })
+                }
+                _ => src.send_datagram(copy),
+            };
+
+            if let Err(e) = res {
+                if self.failed_branch == FailedBranch::Fail {
+                    return Err(e);
+                }
+                ctx.parent
+                    .post_warning(&format!("Removing failed branch {name}: {e}"))?;
+                failed.push(name.clone());
+                last_error = Some(e);
+            }
+        }
+
+        match last_error {
+            Some(e) if failed.len() == branches.len() => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Element for Tee {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: vec![PadTemplate::always("sink", Caps::any())],
+            srcs: vec![PadTemplate::new(
+                "src_%u",
+                PadPresence::Request,
+                Caps::any(),
+            )],
+        }
+    }
+
+    fn properties(&self) -> Vec<PropertySpec> {
+        vec![
+            PropertySpec::enumeration(
+                "blocked-branch",
+                "What to do with data for a branch that is not ready for it",
+                &["wait", "drop"],
+                "wait",
+            )
+            .mutable_in(State::Playing),
+            PropertySpec::enumeration(
+                "failed-branch",
+                "What to do when a branch can not receive data any more",
+                &["remove", "fail"],
+                "remove",
+            )
+            .mutable_in(State::Playing),
+        ]
+    }
+
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        let value = match name {
+            "blocked-branch" => match self.blocked_branch {
+                BlockedBranch::Wait => "wait",
+                BlockedBranch::Drop => "drop",
+            },
+            "failed-branch" => match self.failed_branch {
+                FailedBranch::Remove => "remove",
+                FailedBranch::Fail => "fail",
+            },
+            _ => return None,
+        };
+
+        Some(PropertyValue::Enum(value.to_string()))
+    }
+
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value.as_str()) {
+            ("blocked-branch", Some("drop")) => self.set_blocked_branch(BlockedBranch::Drop),
+            ("blocked-branch", _) => self.set_blocked_branch(BlockedBranch::Wait),
+            ("failed-branch", Some("fail")) => self.set_failed_branch(FailedBranch::Fail),
+            ("failed-branch", _) => self.set_failed_branch(FailedBranch::Remove),
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn query_caps(&self, direction: PadDirection, _pad: &str) -> Caps {
+        match (direction, &self.caps) {
+            (PadDirection::Src, Some(caps)) => caps.clone(),
+            _ => Caps::any(),
+        }
+    }
+
+    fn set_caps(&mut self, direction: PadDirection, _pad: &str, caps: &Caps) -> Result<(), Error> {
+        if direction == PadDirection::Sink {
+            self.caps = Some(caps.clone());
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        let branches = ctx
+            .src_names()
+            .filter(|name| ctx.src(name).is_ok_and(|src| src.is_linked()))
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>();
+        let mut failed = Vec::new();
+
+        loop {
+            match ctx.recv(self)? {
+                Input::Sink(_, Datagram::Message(Message::Quit)) => break,
+                Input::Sink(_, datagram) => self.push(ctx, &branches, &mut failed, datagram)?,
+                Input::Control(_) => return Err(Error::ReceivedInvalidDatagramFromParent),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+element_def! {
+    Tee,
+    "tee",
+    "Generic",
+    "Sends its input to every src pad"
+}
+
+#[cfg(all(test, feature = "element-testsrc", feature = "element-testsink"))]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        caps,
+        elements::{
+            flow::test_util::{collecting_sink, text},
+            misc::{testsink::TestSink, testsrc::TestSrc},
+        },
+        pipeline::{event::Event, graph::PipelineGraph, Data, Pipeline},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_failed_branch() {
+        let testsrc = TestSrc::new(
+            caps::text_utf8(),
+            vec![text("Hello"), text("World"), text("!")],
+        );
+        let (testsink, received) = collecting_sink();
+        // Stops after the first data
+        let failing_sink = TestSink::new(caps::text_utf8(), |_, _| true, |_, _| false);
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let tee = graph.add_element(Tee::new());
+        let failing_sink = graph.add_element(failing_sink);
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc, "src"), (tee, "sink")).unwrap();
+        graph.link((tee, "src_%u"), (failing_sink, "sink")).unwrap();
+        graph.link((tee, "src_%u"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Data::Text(String::from("Hello")),
+                Data::Text(String::from("World")),
+                Data::Text(String::from("!")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocked_branch_drop() {
+        let texts = ["a", "b", "c", "d", "e"];
+        let (release, released) = crossbeam_channel::bounded::<()>(0);
+        let release = Mutex::new(Some(release));
+        let blocked_received = Arc::new(Mutex::new(Vec::new()));
+        let blocked_received_clone = Arc::clone(&blocked_received);
+        let testsrc = TestSrc::new(caps::text_utf8(), texts.map(text).into());
+        let (mut testsink, received) = collecting_sink();
+        testsink.set_on_event(move |event| {
+            if event == Event::Eos {
+                // Hanging up releases the blocked sink
+                release.lock().unwrap().take();
+            }
+        });
+        // Busy until the other branch is done
+        let blocked_sink = TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, data| {
+                let _ = released.recv_timeout(Duration::from_secs(5));
+                blocked_received_clone.lock().unwrap().push(data);
+                true
+            },
+        );
+        let mut tee = Tee::new();
+        tee.set_blocked_branch(BlockedBranch::Drop);
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let tee = graph.add_element(tee);
+        let testsink = graph.add_element(testsink);
+        let blocked_sink = graph.add_element(blocked_sink);
+        graph.link((testsrc, "src"), (tee, "sink")).unwrap();
+        graph
+            .link_with_capacity((tee, "src_%u"), (testsink, "sink"), texts.len())
+            .unwrap();
+        graph
+            .link_with_capacity((tee, "src_%u"), (blocked_sink, "sink"), 1)
+            .unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            texts.map(|text| Data::Text(text.to_string()))
+        );
+        let blocked_received = blocked_received.lock().unwrap();
+        assert!(!blocked_received.is_empty() && blocked_received.len() < texts.len());
+    }
+}
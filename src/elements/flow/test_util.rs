@@ -0,0 +1,66 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers for the tests of the flow elements.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    caps,
+    element_traits::Element,
+    elements::misc::{testsink::TestSink, testsrc::TestSrc},
+    pipeline::{graph::PipelineGraph, Data, Datagram, Pipeline},
+};
+
+pub fn text(text: &str) -> Datagram {
+    Datagram::Data(Data::Text(text.to_string()))
+}
+
+/// A text sink that collects the data it receives.
+pub fn collecting_sink() -> (TestSink, Arc<Mutex<Vec<Data>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = Arc::clone(&received);
+    let testsink = TestSink::new(
+        caps::text_utf8(),
+        |_, _| true,
+        move |_, data| {
+            received_clone.lock().unwrap().push(data);
+            true
+        },
+    );
+
+    (testsink, received)
+}
+
+/// Play `datagrams` through `element`, linked between a [`TestSrc`] and a [`TestSink`] with
+/// the pads `sink` and `src`, and get the data that came out.
+pub fn run_through(element: impl Element + 'static, datagrams: Vec<Datagram>) -> Vec<Data> {
+    let (testsink, received) = collecting_sink();
+
+    let mut graph = PipelineGraph::new();
+    let testsrc = graph.add_element(TestSrc::new(caps::text_utf8(), datagrams));
+    let element = graph.add_element(element);
+    let testsink = graph.add_element(testsink);
+    graph.link((testsrc, "src"), (element, "sink")).unwrap();
+    graph.link((element, "src"), (testsink, "sink")).unwrap();
+
+    let mut pipeline = Pipeline::new(graph);
+    pipeline.play().unwrap();
+    pipeline.wait_eos().unwrap();
+    drop(pipeline);
+
+    let received = std::mem::take(&mut *received.lock().unwrap());
+    received
+}
@@ -15,18 +15,25 @@
 
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufReader, Read, Seek, SeekFrom},
 };
 
 use crate::{
-    debug, element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    caps, element_def,
+    element_traits::{
+        base::{self, Source},
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadDirection, PadTemplate,
+    },
+    pipeline::{
+        error::Error,
+        event::{Event, Segment},
+        query::{Format, Query},
+        state::{State, StateChange},
+        Data, ElementContext,
+    },
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-
 ///```text
 /// +--------------------+
 /// |               _____|
@@ -34,135 +41,207 @@ use crossbeam_channel::{bounded, unbounded, Receiver};
 /// |               ^^^^^|
 /// +--------------------+
 ///```
+///
+/// Reads either the file it was created with, or the file at `location` which is opened when
+/// going to [`State::Ready`].
 pub struct FileSrc {
-    sink: SinkPipe,
-    parent: Parent,
-    reader: BufReader<File>,
+    reader: Option<BufReader<File>>,
+    location: Option<String>,
+    block_size: usize,
 }
 
-impl FileSrc {
-    pub fn new(file: File) -> Self {
+const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+impl Default for FileSrc {
+    fn default() -> Self {
         Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
-            reader: BufReader::new(file),
+            reader: None,
+            location: None,
+            block_size: DEFAULT_BLOCK_SIZE,
         }
     }
+}
 
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::BytesSink {
-            return Err(Error::InvalidSinkType);
-        }
-
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::Bytes {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
+impl FileSrc {
+    pub fn new(file: File) -> Self {
+        Self {
+            reader: Some(BufReader::new(file)),
+            ..Self::default()
         }
+    }
 
-        Err(Error::InvalidSinkType)
+    /// Set the path of the file to read.
+    pub fn set_location(&mut self, location: &str) {
+        self.location = Some(location.to_string());
     }
 
-    fn init(&mut self) -> Result<(), Error> {
-        let (datagram_sender, datagram_receiver) = bounded(0);
-        let (msg_sender, my_msg_receiver) = unbounded();
-        let parent = Parent::new(msg_sender);
-        let mut sink_element = self.sink.take_element()?;
-        sink_element.set_parent(parent);
-        let datagram_receiver_clone = datagram_receiver.clone();
-
-        self.sink.thread_handle = Some(std::thread::spawn(move || {
-            match sink_element.run(datagram_receiver_clone) {
-                Ok(_) => {}
-                Err(e) => error!("Error occurred running sink element: {e}"),
-            }
-        }));
-        self.sink.msg_receiver = Some(my_msg_receiver);
-        self.sink.datagram_sender = Some(datagram_sender);
+    fn reader(&mut self) -> Result<&mut BufReader<File>, Error> {
+        self.reader.as_mut().ok_or(Error::ElementNotReady)
+    }
 
-        Ok(())
+    fn file_size(&self) -> Option<u64> {
+        self.reader
+            .as_ref()
+            .and_then(|reader| reader.get_ref().metadata().ok())
+            .map(|metadata| metadata.len())
     }
 
-    fn run_loop(&mut self) -> bool {
-        let buf = match self.reader.fill_buf() {
-            Ok(buf) => buf.to_vec(),
-            Err(_) => return false,
-        };
+    /// Set the maximum number of bytes to send at a time.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+    }
+}
 
-        if buf.is_empty() {
-            return false;
+impl Source for FileSrc {
+    fn create(&mut self) -> Result<Option<Data>, Error> {
+        let mut buf = vec![0; self.block_size];
+        let n = self
+            .reader()?
+            .read(&mut buf)
+            .map_err(|e| Error::FailedToRead(e.to_string()))?;
+        if n == 0 {
+            return Ok(None);
         }
 
-        self.reader.consume(buf.len());
+        buf.truncate(n);
+        Ok(Some(Data::Bytes(buf)))
+    }
+}
 
-        if self
-            .sink
-            .send_datagram(Datagram::Data(Data::Bytes(buf)))
-            .is_err()
-        {
-            return false;
+impl Element for FileSrc {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: Vec::new(),
+            srcs: vec![PadTemplate::always("src", caps::bytes())],
         }
+    }
 
-        true
+    fn properties(&self) -> Vec<PropertySpec> {
+        vec![
+            PropertySpec::string("location", "Path of the file to read", "")
+                .mutable_in(State::Null),
+            PropertySpec::int(
+                "block-size",
+                "Maximum number of bytes to send at a time",
+                1,
+                i32::MAX.into(),
+                DEFAULT_BLOCK_SIZE as i64,
+            )
+            .mutable_in(State::Playing),
+        ]
     }
-}
 
-impl Element for FileSrc {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::BytesSrc
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        match name {
+            "location" => Some(PropertyValue::Str(
+                self.location.clone().unwrap_or_default(),
+            )),
+            "block-size" => Some(PropertyValue::Int(self.block_size as i64)),
+            _ => None,
+        }
     }
 
-    fn get_architecture(&self) -> ElementArchitecture {
-        ElementArchitecture {
-            sink: Sink::None,
-            srcs: Srcs::One(CommonFormat::Bytes),
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value) {
+            ("location", PropertyValue::Str(location)) => self.set_location(&location),
+            ("block-size", PropertyValue::Int(block_size)) => self.block_size = block_size as usize,
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
         }
+
+        Ok(())
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
-
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Iter => {
-                        if !self.run_loop() {
-                            debug!("Finished");
-                            break;
-                        }
-                        self.parent.send_iter_fin()?;
-                    }
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                _ => return Err(Error::ReceivedInvalidDatagramFromParent),
+    fn change_state(&mut self, change: StateChange) -> Result<(), Error> {
+        match (change, &self.location) {
+            (StateChange::NullToReady, Some(location)) => {
+                let file = File::open(location)
+                    .map_err(|e| Error::FailedToOpen(format!("{location}: {e}")))?;
+                self.reader = Some(BufReader::new(file));
             }
-
-            while let Some(_msg) = self.sink.try_recv_msg()? {
-                // TODO: Handle messages
+            (StateChange::NullToReady, None) if self.reader.is_none() => {
+                return Err(Error::FailedToOpen("No location set".to_string()));
             }
+            // Close the file that was opened from the location
+            (StateChange::ReadyToNull, Some(_)) => self.reader = None,
+            // Start over from the beginning of the file
+            (StateChange::PausedToReady, _) => {
+                self.reader()?
+                    .seek(SeekFrom::Start(0))
+                    .map_err(|_| Error::FailedToSeek)?;
+            }
+            _ => {}
         }
 
-        self.parent.send_finished()
+        Ok(())
     }
 
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
+    fn query(&mut self, _direction: PadDirection, _pad: &str, query: &mut Query) -> bool {
+        match query {
+            Query::Position {
+                format: Format::Bytes,
+                position,
+            } => {
+                *position = self
+                    .reader
+                    .as_mut()
+                    .and_then(|reader| reader.stream_position().ok())
+            }
+            Query::Duration {
+                format: Format::Bytes,
+                duration,
+            } => *duration = self.file_size(),
+            Query::Seeking {
+                format: Format::Bytes,
+                seekable,
+                start,
+                end,
+            } => {
+                *seekable = true;
+                *start = 0;
+                *end = self.file_size();
+            }
+            // Not live and no latency, which is what the query starts out with
+            Query::Latency { .. } => {}
+            _ => return false,
+        }
+
+        true
     }
 
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
+    fn upstream_event(
+        &mut self,
+        ctx: &mut ElementContext,
+        _pad: &str,
+        event: &Event,
+    ) -> Result<bool, Error> {
+        let Event::Seek(seek) = event else {
+            return Ok(false);
+        };
+        if seek.format != Format::Bytes {
+            return Ok(false);
+        }
+
+        ctx.push_event(Event::FlushStart)?;
+        self.reader()?
+            .seek(SeekFrom::Start(seek.position))
+            .map_err(|_| Error::FailedToSeek)?;
+        ctx.push_event(Event::FlushStop)?;
+        ctx.push_event(Event::Segment(Segment::bytes(
+            seek.position,
+            self.file_size(),
+        )))?;
+
+        Ok(true)
+    }
 
-        self.sink.join_thread()
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_source(self, ctx)
     }
 }
 
 element_def! {
     FileSrc,
-    "filesrc"
+    "filesrc",
+    "Source/File",
+    "Reads bytes from a file"
 }
@@ -14,19 +14,17 @@
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+    caps::Caps,
     element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    pipeline::{error::Error, Data, Datagram, Message, Parent},
+    element_traits::{Element, ElementArchitecture, PadTemplate},
+    pipeline::{error::Error, event::Event, Data, Datagram, ElementContext, Input, Message},
 };
 
-use crossbeam_channel::Receiver;
-
 pub struct TestSink {
-    parent: Parent,
-    sink_type: ElementType,
-    sink_format: CommonFormat,
+    caps: Caps,
     on_message: Box<dyn Fn(usize, Message) -> bool + Send + Sync>,
     on_data: Box<dyn Fn(usize, Data) -> bool + Send + Sync>,
+    on_event: Box<dyn Fn(Event) + Send + Sync>,
     message_count: usize,
     data_count: usize,
 }
@@ -40,69 +38,68 @@ pub struct TestSink {
 ///```
 impl TestSink {
     pub fn new(
-        sink_type: ElementType,
-        sink_format: CommonFormat,
+        caps: Caps,
         on_message: impl Fn(usize, Message) -> bool + 'static + Send + Sync,
         on_data: impl Fn(usize, Data) -> bool + 'static + Send + Sync,
     ) -> Self {
         Self {
-            parent: Parent::default(),
-            sink_type,
-            sink_format,
+            caps,
             on_message: Box::new(on_message),
             on_data: Box::new(on_data),
+            on_event: Box::new(|_| {}),
             message_count: 0,
             data_count: 0,
         }
     }
+
+    /// Set the function that is called for every event received
+    pub fn set_on_event(&mut self, on_event: impl Fn(Event) + 'static + Send + Sync) {
+        self.on_event = Box::new(on_event);
+    }
 }
 
-impl Element for TestSink {
-    fn get_sink_type(&self) -> ElementType {
-        self.sink_type.clone()
+impl Default for TestSink {
+    fn default() -> Self {
+        Self::new(Caps::any(), |_, _| true, |_, _| true)
     }
+}
 
+impl Element for TestSink {
     fn get_architecture(&self) -> ElementArchitecture {
         ElementArchitecture {
-            sink: Sink::One(self.sink_format.clone()),
-            srcs: Srcs::None,
+            sinks: vec![PadTemplate::always("sink", self.caps.clone())],
+            srcs: Vec::new(),
         }
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
         loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => {
-                    if !(self.on_message)(self.message_count, msg) {
+            match ctx.recv(self)? {
+                Input::Sink(_, Datagram::Message(msg)) => {
+                    let quit = msg == Message::Quit;
+                    if !(self.on_message)(self.message_count, msg) || quit {
                         break;
                     }
                     self.message_count += 1;
                 }
-                Datagram::Data(data) => {
+                Input::Sink(_, Datagram::Data(data)) => {
                     if !(self.on_data)(self.data_count, data) {
                         break;
                     }
                     self.data_count += 1;
                 }
+                Input::Sink(_, Datagram::Event(event)) => (self.on_event)(event),
+                Input::Control(_) => return Err(Error::ReceivedInvalidDatagramFromParent),
             }
         }
 
         Ok(())
     }
-
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
-    }
-
-    fn cleanup(&mut self) -> Result<(), Error> {
-        Ok(())
-    }
 }
 
 element_def! {
     TestSink,
-    "testsink"
+    "testsink",
+    "Sink/Test",
+    "Accepts anything and calls a function for it"
 }
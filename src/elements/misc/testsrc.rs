@@ -14,14 +14,20 @@
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+    caps::Caps,
     element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error,
-    pipeline::{error::Error, Datagram, Message, Parent, SinkPipe},
+    element_traits::{Element, ElementArchitecture, PadTemplate},
+    pipeline::{
+        error::Error,
+        event::{Event, Segment},
+        state::StateChange,
+        Datagram, ElementContext, Input, Message,
+    },
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-
+/// Starts the stream with [`Event::StreamStart`] and a [`Segment`], then sends one of its
+/// datagrams per iteration and [`Event::Eos`] after the last one.
+///
 ///```text
 /// +-----------------+
 /// |            _____|
@@ -30,131 +36,58 @@ use crossbeam_channel::{bounded, unbounded, Receiver};
 /// +-----------------+
 ///```
 pub struct TestSrc {
-    sink: SinkPipe,
-    parent: Parent,
-    sink_element_type: ElementType,
-    sink_format: CommonFormat,
+    caps: Caps,
     index: usize,
     datagrams: Vec<Datagram>,
 }
 
 impl TestSrc {
-    pub fn new(
-        sink_element_type: ElementType,
-        sink_format: CommonFormat,
-        datagrams: Vec<Datagram>,
-    ) -> Self {
+    pub fn new(caps: Caps, datagrams: Vec<Datagram>) -> Self {
         Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
-            sink_element_type,
-            sink_format,
-            datagrams: datagrams,
+            caps,
+            datagrams,
             index: 0,
         }
     }
+}
 
-    /// Link the sink element.
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != self.sink_element_type {
-            return Err(Error::InvalidSinkType);
-        }
-
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == self.sink_format {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
+impl Element for TestSrc {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: Vec::new(),
+            srcs: vec![PadTemplate::always("src", self.caps.clone())],
         }
-
-        Err(Error::InvalidSinkType)
     }
 
-    fn run_loop(&mut self, datagram: Datagram) -> bool {
-        if let Err(e) = self.sink.send_datagram(datagram) {
-            error!("{e}");
-            return false;
+    fn change_state(&mut self, change: StateChange) -> Result<(), Error> {
+        if change == StateChange::PausedToReady {
+            self.index = 0;
         }
 
-        true
-    }
-
-    fn init(&mut self) -> Result<(), Error> {
-        let (datagram_sender, datagram_receiver) = bounded(0);
-        let (msg_sender, my_msg_receiver) = unbounded();
-        let parent = Parent::new(msg_sender);
-        let mut sink_element = self.sink.take_element()?;
-        sink_element.set_parent(parent);
-        let datagram_receiver_clone = datagram_receiver.clone();
-
-        self.sink.thread_handle = Some(std::thread::spawn(move || {
-            match sink_element.run(datagram_receiver_clone) {
-                Ok(_) => {}
-                Err(e) => error!("Error occurred running sink element: {e}"),
-            }
-        }));
-        self.sink.msg_receiver = Some(my_msg_receiver);
-        self.sink.datagram_sender = Some(datagram_sender);
-
         Ok(())
     }
-}
-
-impl Element for TestSrc {
-    fn get_sink_type(&self) -> ElementType {
-        // TODO: change to some custom shit
-        ElementType::TextSrc
-    }
 
-    fn get_architecture(&self) -> ElementArchitecture {
-        ElementArchitecture {
-            sink: Sink::None,
-            srcs: Srcs::One(self.sink_format.clone()),
-        }
-    }
-
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        ctx.push_event(Event::StreamStart)?;
+        ctx.push_event(Event::Segment(Segment::default()))?;
 
         loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Iter => {
-                        if self.index >= self.datagrams.len() {
-                            break;
-                        }
-                        if !self.run_loop(self.datagrams[self.index].clone()) {
-                            break;
-                        }
-                        self.index += 1;
-                        self.parent.send_iter_fin()?;
+            match ctx.recv(self)? {
+                Input::Control(Message::Iter) => {
+                    if self.index >= self.datagrams.len() {
+                        ctx.push_event(Event::Eos)?;
+                        break;
                     }
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
+                    ctx.push("src", self.datagrams[self.index].try_clone()?)?;
+                    self.index += 1;
+                    ctx.iter_fin()?;
+                }
+                Input::Control(Message::Quit) => break,
                 _ => return Err(Error::ReceivedInvalidDatagramFromParent),
             }
-
-            while let Some(_msg) = self.sink.try_recv_msg()? {
-                // TODO: Handle messages
-            }
         }
 
-        self.parent.send_finished()
-    }
-
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
-    }
-
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
-
-        self.sink.join_thread()
+        Ok(())
     }
 }
 
@@ -166,8 +99,9 @@ element_def! {
 #[cfg(test)]
 mod tests {
     use crate::{
+        caps,
         elements::misc::testsink::TestSink,
-        pipeline::{self, Data, Pipeline},
+        pipeline::{graph::PipelineGraph, Data, Pipeline},
     };
 
     use super::*;
@@ -175,13 +109,12 @@ mod tests {
     #[test]
     fn test_basic() {
         let testsink = TestSink::new(
-            ElementType::TextSink,
-            CommonFormat::Text,
+            caps::text_utf8(),
             |_, _| true,
             |n, data| {
                 match n {
-                    1 => assert_eq!(data, Data::Text(String::from("Hello"))),
-                    2 => assert_eq!(data, Data::Text(String::from("World"))),
+                    0 => assert_eq!(data, Data::Text(String::from("Hello"))),
+                    1 => assert_eq!(data, Data::Text(String::from("World"))),
                     _ => unreachable!(),
                 }
                 true
@@ -189,16 +122,40 @@ mod tests {
         );
         let datagrams = vec![
             Datagram::Data(Data::Text(String::from("Hello"))),
-            Datagram::Data(Data::Text(String::from("Hello"))),
+            Datagram::Data(Data::Text(String::from("World"))),
         ];
-        let mut testsrc = TestSrc::new(ElementType::TextSink, CommonFormat::Text, datagrams);
-        testsrc.link_sink_element(testsink).unwrap();
+        let testsrc = TestSrc::new(caps::text_utf8(), datagrams);
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc, "src"), (testsink, "sink")).unwrap();
 
-        let mut pipeline = Pipeline::new(testsrc);
+        let mut pipeline = Pipeline::new(graph);
         pipeline.init().unwrap();
 
         for _ in 0..2 {
             pipeline.iter().unwrap();
         }
     }
+
+    #[test]
+    fn test_push_error() {
+        // Stops after the first data, so pushing the second fails
+        let testsink = TestSink::new(caps::text_utf8(), |_, _| true, |_, _| false);
+        let datagrams = vec![
+            Datagram::Data(Data::Text(String::from("Hello"))),
+            Datagram::Data(Data::Text(String::from("World"))),
+        ];
+        let testsrc = TestSrc::new(caps::text_utf8(), datagrams);
+
+        let mut graph = PipelineGraph::new();
+        let testsrc = graph.add_element(testsrc);
+        let testsink = graph.add_element(testsink);
+        graph.link((testsrc, "src"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        assert!(pipeline.wait_eos().is_err());
+    }
 }
@@ -13,8 +13,12 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "elements-av")]
+pub mod av;
 #[cfg(feature = "elements-conversion")]
 pub mod conversion;
+#[cfg(feature = "elements-flow")]
+pub mod flow;
 #[cfg(feature = "elements-io")]
 pub mod io;
 #[cfg(feature = "elements-misc")]
@@ -22,4 +26,33 @@ pub mod misc;
 #[cfg(feature = "elements-text")]
 pub mod text;
 
-pub mod av;
+use crate::registry::ElementRegistry;
+
+/// Register the factories of every element that is enabled with its cargo feature.
+#[allow(unused_variables)]
+pub(crate) fn register(registry: &mut ElementRegistry) {
+    #[cfg(feature = "element-audiodecoder")]
+    registry.register(av::audiodecoder::AudioDecoder::factory());
+    #[cfg(feature = "element-demuxsrc")]
+    registry.register(av::demuxsrc::DemuxSrc::factory());
+    #[cfg(feature = "element-videodecoder")]
+    registry.register(av::videodecoder::VideoDecoder::factory());
+    #[cfg(feature = "element-bytes2text")]
+    registry.register(conversion::bytes2text::Bytes2Text::factory());
+    #[cfg(feature = "element-funnel")]
+    registry.register(flow::funnel::Funnel::factory());
+    #[cfg(feature = "element-inputselector")]
+    registry.register(flow::inputselector::InputSelector::factory());
+    #[cfg(feature = "element-queue")]
+    registry.register(flow::queue::Queue::factory());
+    #[cfg(feature = "element-tee")]
+    registry.register(flow::tee::Tee::factory());
+    #[cfg(feature = "element-filesrc")]
+    registry.register(io::filesrc::FileSrc::factory());
+    #[cfg(feature = "element-testsink")]
+    registry.register(misc::testsink::TestSink::factory());
+    #[cfg(feature = "element-stdoutlog")]
+    registry.register(text::stdoutlog::StdoutLog::factory());
+    #[cfg(feature = "element-texttestsrc")]
+    registry.register(text::texttestsrc::TextTestSrc::factory());
+}
@@ -13,17 +13,18 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::io::Write;
+
 use crate::{
-    element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    pipeline::{error::Error, Data, Datagram, Message, Parent},
+    caps, element_def,
+    element_traits::{
+        base::{self, Sink},
+        Element, ElementArchitecture, PadTemplate,
+    },
+    pipeline::{error::Error, event::Event, Data, ElementContext},
 };
 
-use crossbeam_channel::Receiver;
-
-pub struct StdoutLog {
-    parent: Parent,
-}
+pub struct StdoutLog {}
 
 impl Default for StdoutLog {
     fn default() -> Self {
@@ -40,64 +41,45 @@ impl Default for StdoutLog {
 ///```
 impl StdoutLog {
     pub fn new() -> Self {
-        Self {
-            parent: Parent::default(),
-        }
-    }
-
-    fn run_loop(&self, text: String) -> bool {
-        print!("{text}");
-
-        true
+        Self {}
     }
 }
 
-impl Element for StdoutLog {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::TextSink
-    }
-
-    fn get_architecture(&self) -> ElementArchitecture {
-        ElementArchitecture {
-            sink: Sink::One(CommonFormat::Text),
-            srcs: Srcs::None,
+impl Sink for StdoutLog {
+    fn render(&mut self, data: Data) -> Result<(), Error> {
+        match data {
+            Data::Text(text) => print!("{text}"),
+            _ => return Err(Error::ReceivedInvalidDatagramFromParent),
         }
+
+        Ok(())
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                Datagram::Data(data) => match data {
-                    Data::Text(text) => {
-                        if !self.run_loop(text) {
-                            break;
-                        }
-                    }
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-            }
+    fn sink_event(&mut self, event: &Event) -> Result<(), Error> {
+        if *event == Event::Eos {
+            let _ = std::io::stdout().flush();
         }
 
         Ok(())
     }
+}
 
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
+impl Element for StdoutLog {
+    fn get_architecture(&self) -> ElementArchitecture {
+        ElementArchitecture {
+            sinks: vec![PadTemplate::always("sink", caps::text_utf8())],
+            srcs: Vec::new(),
+        }
     }
 
-    fn cleanup(&mut self) -> Result<(), Error> {
-        Ok(())
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_sink(self, ctx)
     }
 }
 
 element_def! {
     StdoutLog,
-    "stdoutlog"
+    "stdoutlog",
+    "Sink/Text",
+    "Prints text to stdout"
 }
@@ -14,14 +14,15 @@
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
-    debug, element_def,
-    element_traits::{CommonFormat, Element, ElementArchitecture, ElementType, Sink, Srcs},
-    error,
-    pipeline::{error::Error, Data, Datagram, Message, Parent, SinkPipe},
+    caps, element_def,
+    element_traits::{
+        base::{self, Source},
+        property::{PropertySpec, PropertyValue},
+        Element, ElementArchitecture, PadTemplate,
+    },
+    pipeline::{error::Error, state::State, Data, ElementContext},
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver};
-
 /// Text src that sends a [`Data::Text`] packet to the src.
 ///
 ///```text
@@ -36,15 +37,18 @@ use crossbeam_channel::{bounded, unbounded, Receiver};
 /// ```
 ///use streamcraft::{
 ///    elements::text::{stdoutlog::StdoutLog, texttestsrc::TextTestSrc},
-///    pipeline::Pipeline,
+///    pipeline::{graph::PipelineGraph, Pipeline},
 ///};
 ///
-///let stdoutlog = StdoutLog::new();
 ///let mut texttest = TextTestSrc::new();
-///texttest.link_sink_element(stdoutlog).unwrap();
 ///texttest.set_text_to_send("Texttestsrc example".to_string());
 ///
-///let mut pipeline = Pipeline::new(texttest);
+///let mut graph = PipelineGraph::new();
+///let texttest = graph.add_element(texttest);
+///let stdoutlog = graph.add_element(StdoutLog::new());
+///graph.link((texttest, "src"), (stdoutlog, "sink")).unwrap();
+///
+///let mut pipeline = Pipeline::new(graph);
 ///pipeline.init().unwrap();
 ///
 ///for _ in 0..3 {
@@ -52,8 +56,6 @@ use crossbeam_channel::{bounded, unbounded, Receiver};
 ///}
 /// ```
 pub struct TextTestSrc {
-    sink: SinkPipe,
-    parent: Parent,
     text_to_send: String,
 }
 
@@ -66,127 +68,68 @@ impl Default for TextTestSrc {
 impl TextTestSrc {
     pub fn new() -> Self {
         Self {
-            sink: SinkPipe::default(),
-            parent: Parent::default(),
             text_to_send: String::from("Test\n"),
         }
     }
 
-    /// Link the sink element.
-    pub fn link_sink_element(&mut self, sink: impl Element + 'static) -> Result<(), Error> {
-        if sink.get_sink_type() != ElementType::TextSink {
-            return Err(Error::InvalidSinkType);
-        }
-
-        if let Sink::One(format) = sink.get_architecture().sink {
-            if format == CommonFormat::Text {
-                self.sink.set_element(sink);
-                return Ok(());
-            }
-        }
-
-        Err(Error::InvalidSinkType)
-    }
-
     /// Set the text to send to sink
     pub fn set_text_to_send(&mut self, text: String) {
         self.text_to_send = text;
     }
+}
 
-    fn run_loop(&mut self) -> bool {
-        // TODO: use send_datagram() and return error
-        if let Some(sender) = &self.sink.datagram_sender {
-            if let Err(e) = sender.send(Datagram::Data(Data::Text(self.text_to_send.clone()))) {
-                error!("{e}");
-                return false;
-            }
-        }
-
-        true
-    }
-
-    fn init(&mut self) -> Result<(), Error> {
-        let (datagram_sender, datagram_receiver) = bounded(0);
-        let (msg_sender, my_msg_receiver) = unbounded();
-        let parent = Parent::new(msg_sender);
-        let mut sink_element = self.sink.take_element()?;
-        sink_element.set_parent(parent);
-        let datagram_receiver_clone = datagram_receiver.clone();
-
-        self.sink.thread_handle = Some(std::thread::spawn(move || {
-            match sink_element.run(datagram_receiver_clone) {
-                Ok(_) => {}
-                Err(e) => error!("Error occurred running sink element: {e}"),
-            }
-        }));
-        self.sink.msg_receiver = Some(my_msg_receiver);
-        self.sink.datagram_sender = Some(datagram_sender);
-
-        Ok(())
+impl Source for TextTestSrc {
+    fn create(&mut self) -> Result<Option<Data>, Error> {
+        Ok(Some(Data::Text(self.text_to_send.clone())))
     }
 }
 
 impl Element for TextTestSrc {
-    fn get_sink_type(&self) -> ElementType {
-        ElementType::TextSrc
-    }
-
     fn get_architecture(&self) -> ElementArchitecture {
         ElementArchitecture {
-            sink: Sink::None,
-            srcs: Srcs::One(CommonFormat::Text),
+            sinks: Vec::new(),
+            srcs: vec![PadTemplate::always("src", caps::text_utf8())],
         }
     }
 
-    fn run(&mut self, parent_datagram_receiver: Receiver<Datagram>) -> Result<(), Error> {
-        self.init()?;
-
-        loop {
-            match parent_datagram_receiver
-                .recv()
-                .map_err(|_| Error::FailedToRecvFromParent)?
-            {
-                Datagram::Message(msg) => match msg {
-                    Message::Iter => {
-                        if !self.run_loop() {
-                            break;
-                        }
-                        self.parent.send_iter_fin()?;
-                    }
-                    Message::Quit => break,
-                    _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-                },
-                _ => return Err(Error::ReceivedInvalidDatagramFromParent),
-            }
-
-            while let Some(_msg) = self.sink.try_recv_msg()? {
-                // TODO: Handle messages
-            }
-        }
-
-        self.parent.send_finished()
+    fn properties(&self) -> Vec<PropertySpec> {
+        vec![PropertySpec::string("text", "The text to send", "Test\n").mutable_in(State::Playing)]
     }
 
-    fn set_parent(&mut self, parent: Parent) {
-        self.parent = parent;
+    fn property(&self, name: &str) -> Option<PropertyValue> {
+        match name {
+            "text" => Some(PropertyValue::Str(self.text_to_send.clone())),
+            _ => None,
+        }
     }
 
-    fn cleanup(&mut self) -> Result<(), Error> {
-        self.sink.send_quit()?;
-        self.sink.drop_data_sender();
+    fn set_property(&mut self, name: &str, value: PropertyValue) -> Result<(), Error> {
+        match (name, value) {
+            ("text", PropertyValue::Str(text)) => self.set_text_to_send(text),
+            _ => return Err(Error::NoSuchProperty(name.to_string())),
+        }
+
+        Ok(())
+    }
 
-        self.sink.join_thread()
+    fn run(&mut self, ctx: &mut ElementContext) -> Result<(), Error> {
+        base::run_source(self, ctx)
     }
 }
 
 element_def! {
     TextTestSrc,
-    "texttestsrc"
+    "texttestsrc",
+    "Source/Text",
+    "Sends the same text every iteration"
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{elements::misc::testsink, pipeline::Pipeline};
+    use crate::{
+        elements::misc::testsink,
+        pipeline::{graph::PipelineGraph, Pipeline},
+    };
 
     use super::*;
 
@@ -194,22 +137,24 @@ mod tests {
     fn test_basic() {
         let test_text_data = "Test";
         let testsink = testsink::TestSink::new(
-            ElementType::TextSink,
-            CommonFormat::Text,
+            caps::text_utf8(),
             |_, _| true,
             |_, data| {
-                match data {
-                    Data::Text(text) => assert_eq!(text, String::from("Test")),
-                    _ => {}
+                if let Data::Text(text) = data {
+                    assert_eq!(text, String::from("Test"));
                 }
                 true
             },
         );
         let mut textsrc = TextTestSrc::new();
         textsrc.set_text_to_send(String::from(test_text_data));
-        textsrc.link_sink_element(testsink).unwrap();
 
-        let mut pipeline = Pipeline::new(textsrc);
+        let mut graph = PipelineGraph::new();
+        let textsrc = graph.add_element(textsrc);
+        let testsink = graph.add_element(testsink);
+        graph.link((textsrc, "src"), (testsink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
         pipeline.init().unwrap();
         pipeline.iter().unwrap();
     }
@@ -22,26 +22,30 @@
 //!```
 //!use streamcraft::{
 //!    elements::text::{stdoutlog::StdoutLog, texttestsrc::TextTestSrc},
-//!    pipeline::Pipeline,
+//!    pipeline::{graph::PipelineGraph, Pipeline},
 //!};
 //!
-//!// Create text sink element that only prints text to stdout
-//!let stdoutlog = StdoutLog::new();
-//!
 //!let mut texttest = TextTestSrc::new();
-//!// Link the printing element with out text src
-//!texttest.link_sink_element(stdoutlog).unwrap();
 //!// Set the text we wish to print
 //!texttest.set_text_to_send("Hello, World!\n".to_string());
 //!
-//!let mut pipeline = Pipeline::new(texttest);
+//!let mut graph = PipelineGraph::new();
+//!let texttest = graph.add_element(texttest);
+//!// Create text sink element that only prints text to stdout
+//!let stdoutlog = graph.add_element(StdoutLog::new());
+//!// Link the printing element with out text src
+//!graph.link((texttest, "src"), (stdoutlog, "sink")).unwrap();
+//!
+//!let mut pipeline = Pipeline::new(graph);
 //!pipeline.init().unwrap();
 //!
 //!// Perform one iteration of the pipeline
 //!pipeline.iter().unwrap();
 //!```
 
+pub mod caps;
 pub mod element_traits;
 pub mod elements;
 pub mod log;
 pub mod pipeline;
+pub mod registry;
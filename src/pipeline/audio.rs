@@ -0,0 +1,159 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Raw audio.
+
+use std::time::Duration;
+
+use crate::caps::{self, Caps, Structure};
+
+/// How the samples of the channels are stored.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AudioLayout {
+    /// One plane with the samples of all channels after each other.
+    Interleaved,
+    /// One plane per channel.
+    Planar,
+}
+
+impl AudioLayout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Interleaved => "interleaved",
+            Self::Planar => "planar",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "interleaved" => Some(Self::Interleaved),
+            "planar" => Some(Self::Planar),
+            _ => None,
+        }
+    }
+}
+
+/// The format of raw audio.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct AudioFormat {
+    /// Name of the libav sample format, e.g. `s16` or `fltp`.
+    pub sample_format: String,
+    /// Samples per second.
+    pub sample_rate: u32,
+    pub channels: u32,
+    /// Description of the libav channel layout, e.g. `stereo` or `5.1`.
+    pub channel_layout: String,
+    pub layout: AudioLayout,
+}
+
+impl AudioFormat {
+    /// Fixed [`caps::AUDIO_RAW`] caps for this format.
+    pub fn caps(&self) -> Caps {
+        Caps::new(
+            Structure::new(caps::AUDIO_RAW)
+                .field("format", self.sample_format.as_str())
+                .field("rate", self.sample_rate)
+                .field("channels", self.channels)
+                .field("channel-layout", self.channel_layout.as_str())
+                .field("layout", self.layout.name()),
+        )
+    }
+
+    /// The format in fixed [`caps::AUDIO_RAW`] caps, `None` if the caps are not raw audio or
+    /// a field is missing.
+    pub fn from_caps(caps: &Caps) -> Option<Self> {
+        let structure = caps.structure()?;
+        if structure.name != caps::AUDIO_RAW {
+            return None;
+        }
+
+        Some(Self {
+            sample_format: structure.get_str("format")?.to_string(),
+            sample_rate: structure.get_int("rate")?.try_into().ok()?,
+            channels: structure.get_int("channels")?.try_into().ok()?,
+            channel_layout: structure.get_str("channel-layout")?.to_string(),
+            layout: AudioLayout::from_name(structure.get_str("layout")?)?,
+        })
+    }
+}
+
+/// Decoded audio.
+#[derive(PartialEq, Clone, Debug)]
+pub struct AudioBuffer {
+    pub format: AudioFormat,
+    /// Samples per channel.
+    pub samples: usize,
+    /// One plane per channel if the layout is [`AudioLayout::Planar`], otherwise one.
+    pub planes: Vec<Vec<u8>>,
+    /// Presentation time, `None` if unknown.
+    pub pts: Option<Duration>,
+}
+
+impl AudioBuffer {
+    /// Size of the data of all planes in bytes.
+    pub fn size(&self) -> usize {
+        self.planes.iter().map(Vec::len).sum()
+    }
+
+    /// How long the samples play for, `None` if the sample rate is unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        if self.format.sample_rate == 0 {
+            return None;
+        }
+
+        Some(Duration::from_nanos(
+            (self.samples as u128 * 1_000_000_000 / self.format.sample_rate as u128) as u64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_caps() {
+        let format = AudioFormat {
+            sample_format: "fltp".to_string(),
+            sample_rate: 48000,
+            channels: 2,
+            channel_layout: "stereo".to_string(),
+            layout: AudioLayout::Planar,
+        };
+
+        assert!(format.caps().is_fixed());
+        assert_eq!(AudioFormat::from_caps(&format.caps()), Some(format));
+        assert_eq!(AudioFormat::from_caps(&caps::audio_raw()), None);
+    }
+
+    #[test]
+    fn test_duration() {
+        let buffer = AudioBuffer {
+            format: AudioFormat {
+                sample_format: "s16".to_string(),
+                sample_rate: 48000,
+                channels: 2,
+                channel_layout: "stereo".to_string(),
+                layout: AudioLayout::Interleaved,
+            },
+            samples: 1024,
+            planes: vec![vec![0; 1024 * 2 * 2]],
+            pts: None,
+        };
+
+        assert_eq!(buffer.size(), 4096);
+        assert_eq!(buffer.duration(), Some(Duration::from_nanos(21_333_333)));
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{sync::Arc, time::Duration};
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError, TrySendError};
+
+use crate::caps::Structure;
+
+use super::{error::Error, graph::NodeId, state::State};
+
+/// Notifications posted by the pipeline and its elements while it is running.
+///
+/// `source` is the element that posted the message, e.g. `filesrc0`, or `pipeline`.
+#[derive(Clone, Debug)]
+pub enum BusMessage {
+    Error {
+        source: String,
+        error: Arc<Error>,
+    },
+    Warning {
+        source: String,
+        message: String,
+    },
+    Info {
+        source: String,
+        message: String,
+    },
+    /// Every element has stopped running and none of them failed.
+    Eos,
+    StateChanged {
+        source: String,
+        old: State,
+        new: State,
+    },
+    /// How far an element has come with some work, in percent.
+    Progress {
+        source: String,
+        percent: u8,
+    },
+    /// Application specific message.
+    Application {
+        source: String,
+        structure: Structure,
+    },
+    /// An element has stopped running.
+    ElementFinished(NodeId),
+}
+
+/// How many messages the bus holds before the oldest are dropped.
+pub const BUS_CAPACITY: usize = 1024;
+
+/// Create a bus and the sender that posts on it.
+pub(crate) fn channel() -> (BusSender, Bus) {
+    let (sender, receiver) = bounded(BUS_CAPACITY);
+    (
+        BusSender {
+            sender,
+            receiver: receiver.clone(),
+        },
+        Bus { receiver },
+    )
+}
+
+/// Posts [`BusMessage`]s on a [`Bus`]. When nobody reads the bus and it is full, the oldest
+/// message is dropped to make room, so posting never blocks.
+#[derive(Clone)]
+pub struct BusSender {
+    sender: Sender<BusMessage>,
+    /// Used to drop the oldest message.
+    receiver: Receiver<BusMessage>,
+}
+
+impl BusSender {
+    pub fn send(&self, msg: BusMessage) -> Result<(), Error> {
+        let mut msg = msg;
+        loop {
+            match self.sender.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(unsent)) => {
+                    // Someone else may have read the bus in the meantime, which is fine
+                    let _ = self.receiver.try_recv();
+                    msg = unsent;
+                }
+                Err(TrySendError::Disconnected(_)) => return Err(Error::MessageParentFailed),
+            }
+        }
+    }
+}
+
+/// Receives [`BusMessage`]s from a [`Pipeline`](super::Pipeline) on any thread.
+#[derive(Clone)]
+pub struct Bus {
+    receiver: Receiver<BusMessage>,
+}
+
+impl Bus {
+    /// Block until a message is posted.
+    pub fn recv(&self) -> Result<BusMessage, Error> {
+        self.receiver.recv().map_err(|_| Error::FailedToRecvFromBus)
+    }
+
+    /// Block until a message is posted or `timeout` has passed.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<BusMessage>, Error> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(msg) => Ok(Some(msg)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::FailedToRecvFromBus),
+        }
+    }
+
+    /// Get the next message if there is one.
+    pub fn try_recv(&self) -> Result<Option<BusMessage>, Error> {
+        match self.receiver.try_recv() {
+            Ok(msg) => Ok(Some(msg)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(Error::FailedToRecvFromBus),
+        }
+    }
+
+    /// Iterate over messages, blocking until the next one is posted.
+    pub fn iter(&self) -> impl Iterator<Item = BusMessage> + '_ {
+        self.receiver.iter()
+    }
+
+    /// Iterate over the messages that have already been posted.
+    pub fn try_iter(&self) -> impl Iterator<Item = BusMessage> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest() {
+        let (sender, bus) = channel();
+        for percent in 0..=BUS_CAPACITY {
+            sender
+                .send(BusMessage::Progress {
+                    source: String::from("test"),
+                    percent: (percent % 100) as u8,
+                })
+                .unwrap();
+        }
+
+        let messages = bus.try_iter().collect::<Vec<_>>();
+        assert_eq!(messages.len(), BUS_CAPACITY);
+        assert!(matches!(
+            messages.first(),
+            Some(BusMessage::Progress { percent: 1, .. })
+        ));
+    }
+}
@@ -13,19 +13,39 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::sync::Arc;
+
 use crossbeam_channel::SendError;
 
-use super::Datagram;
+use crate::caps::Caps;
+
+use super::{state::State, Datagram};
 
 #[derive(Debug)]
 pub enum Error {
-    NoThreadHandle,
     FailedToJoinThread,
     NoSinkMessageSender,
-    NoSinkMessageReceiver,
     MessageSinkFailed,
     NoSinkElement,
     PipelineNotReady,
+    PipelineAlreadyInitialized,
+    PipelineFinished,
+    PipelineNotPlaying,
+    PipelineNotFreeRunning,
+    FailedToRecvFromBus,
+    FailedToSendQuery,
+    ElementNotReady,
+    FailedToSeek,
+    QueuePoisoned,
+    NoSuchProperty(String),
+    PropertyNotReadable(String),
+    PropertyNotWritable(String),
+    PropertyNotMutable(String, State),
+    InvalidPropertyValue(String),
+    NoSuchElement(String),
+    FailedToOpen(String),
+    FailedToRead(String),
+    InvalidData(String),
     NoSinkDatagramSender,
     FailedToRecvFromParent,
     ReceivedInvalidDatagramFromParent,
@@ -33,13 +53,66 @@ pub enum Error {
     ReceiveFromSinkFailed,
     MessageParentFailed,
     NoParentMessageSender,
-    InvalidSinkType,
+    NoCommonFormat {
+        src: String,
+        sink: String,
+        src_caps: Caps,
+        sink_caps: Caps,
+    },
     FailedToSendDatagramToSink,
+    NoSuchNode,
+    NoSuchPad,
+    PadAlreadyLinked,
+    LinkCreatesCycle,
+    UnlinkedSinkPad,
     AVError(libav::error::Error),
     SendError(SendError<Datagram>),
+    /// The element at `path`, e.g. `filesrc0`, failed while running.
+    Element {
+        path: String,
+        error: Arc<Error>,
+    },
+    /// Sending on the pad at `path`, e.g. `filesrc0:src`, failed.
+    Pad {
+        path: String,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The error that is wrapped by [`Error::Element`] and [`Error::Pad`].
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Self::Element { error, .. } => error.root_cause(),
+            Self::Pad { error, .. } => error.root_cause(),
+            e => e,
+        }
+    }
+
+    /// Whether the error only happened because an element that was linked to the failing one
+    /// had already stopped.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(
+            self.root_cause(),
+            Self::SendError(_)
+                | Self::MessageSinkFailed
+                | Self::NoSinkDatagramSender
+                | Self::FailedToRecvFromParent
+                | Self::FailedToSendDatagramToSink
+        )
+    }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Element { error, .. } => Some(error.as_ref()),
+            Self::Pad { error, .. } => Some(error.as_ref()),
+            Self::AVError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -47,13 +120,31 @@ impl std::fmt::Display for Error {
             f,
             "{}",
             match self {
-                Self::NoThreadHandle => "No thread handle".to_string(),
                 Self::FailedToJoinThread => "Failed to join thread".to_string(),
                 Self::NoSinkMessageSender => "No sink message sender".to_string(),
-                Self::NoSinkMessageReceiver => "No sink message receiver".to_string(),
                 Self::MessageSinkFailed => "Message sink failed".to_string(),
                 Self::NoSinkElement => "No sink element".to_string(),
                 Self::PipelineNotReady => "Pipeline is not ready".to_string(),
+                Self::PipelineAlreadyInitialized => "Pipeline is already initialized".to_string(),
+                Self::PipelineFinished => "Pipeline is finished".to_string(),
+                Self::PipelineNotPlaying => "Pipeline is not playing".to_string(),
+                Self::PipelineNotFreeRunning => "Pipeline is not free running".to_string(),
+                Self::FailedToRecvFromBus => "Failed to receive from bus".to_string(),
+                Self::FailedToSendQuery => "Failed to send query".to_string(),
+                Self::ElementNotReady => "Element is not ready".to_string(),
+                Self::FailedToSeek => "Failed to seek".to_string(),
+                Self::QueuePoisoned => "Queue is poisoned".to_string(),
+                Self::NoSuchProperty(name) => format!("No such property: {name}"),
+                Self::PropertyNotReadable(name) => format!("Property {name} is not readable"),
+                Self::PropertyNotWritable(name) => format!("Property {name} is not writable"),
+                Self::PropertyNotMutable(name, state) => {
+                    format!("Property {name} can not be changed in state {state}")
+                }
+                Self::InvalidPropertyValue(value) => format!("Invalid property value: {value}"),
+                Self::NoSuchElement(name) => format!("No such element: {name}"),
+                Self::FailedToOpen(e) => format!("Failed to open: {e}"),
+                Self::FailedToRead(e) => format!("Failed to read: {e}"),
+                Self::InvalidData(e) => format!("Invalid data: {e}"),
                 Self::NoSinkDatagramSender => "No sink datagram sender".to_string(),
                 Self::FailedToRecvFromParent => "Failed to recv from parent".to_string(),
                 Self::ReceivedInvalidDatagramFromParent =>
@@ -63,10 +154,23 @@ impl std::fmt::Display for Error {
                 Self::ReceiveFromSinkFailed => "Receive from sink failed".to_string(),
                 Self::MessageParentFailed => "Message parent failed".to_string(),
                 Self::NoParentMessageSender => "No parent message sender".to_string(),
-                Self::InvalidSinkType => "Invalid sink type".to_string(),
+                Self::NoCommonFormat {
+                    src,
+                    sink,
+                    src_caps,
+                    sink_caps,
+                } =>
+                    format!("No common format between {src} ({src_caps}) and {sink} ({sink_caps})"),
                 Self::FailedToSendDatagramToSink => "Failed to send datagram to sink".to_string(),
+                Self::NoSuchNode => "No such node".to_string(),
+                Self::NoSuchPad => "No such pad".to_string(),
+                Self::PadAlreadyLinked => "Pad is already linked".to_string(),
+                Self::LinkCreatesCycle => "Link creates a cycle".to_string(),
+                Self::UnlinkedSinkPad => "Unlinked sink pad".to_string(),
                 Self::AVError(e) => format!("AVError: {e}"),
                 Self::SendError(e) => format!("SendError: {e}"),
+                Self::Element { path, error } => format!("{path}: {error}"),
+                Self::Pad { path, error } => format!("{path}: {error}"),
             }
         )
     }
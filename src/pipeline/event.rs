@@ -0,0 +1,107 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use crate::caps::{Caps, Structure};
+
+use super::query::Format;
+
+/// The part of the stream that the data following a [`Event::Segment`] belongs to.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Segment {
+    /// The unit of `start` and `stop`.
+    pub format: Format,
+    pub start: u64,
+    /// `None` if the end is not known.
+    pub stop: Option<u64>,
+}
+
+impl Segment {
+    pub fn time(start: Duration, stop: Option<Duration>) -> Self {
+        Self {
+            format: Format::Time,
+            start: start.as_nanos() as u64,
+            stop: stop.map(|stop| stop.as_nanos() as u64),
+        }
+    }
+
+    pub fn bytes(start: u64, stop: Option<u64>) -> Self {
+        Self {
+            format: Format::Bytes,
+            start,
+            stop,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct SeekFlags {
+    /// Seek to the keyframe at or before the position. Data before the position is not dropped.
+    pub key_unit: bool,
+    /// Drop everything before the position after seeking to the keyframe before it.
+    pub accurate: bool,
+}
+
+/// Request to continue the stream from `position`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Seek {
+    pub format: Format,
+    pub position: u64,
+    pub flags: SeekFlags,
+    /// Identifies the seek, so that an element that gets it through more than one pad only
+    /// seeks once.
+    pub seqnum: u32,
+}
+
+impl Seek {
+    pub fn new(format: Format, position: u64, flags: SeekFlags) -> Self {
+        static SEQNUM: AtomicU32 = AtomicU32::new(0);
+
+        Self {
+            format,
+            position,
+            flags,
+            seqnum: SEQNUM.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Most events travel downstream in order with the data, see
+/// [`Datagram::Event`](super::Datagram::Event). Elements that do not care about an event should
+/// forward it with [`ElementContext::push_event`](super::ElementContext::push_event).
+///
+/// [`Event::Seek`] travels upstream instead, see [`Element::upstream_event`].
+///
+/// [`Element::upstream_event`]: crate::element_traits::Element::upstream_event
+#[derive(PartialEq, Clone, Debug)]
+pub enum Event {
+    /// The first thing sent by a source when it starts.
+    StreamStart,
+    Segment(Segment),
+    /// Drop any data that is buffered until [`Event::FlushStop`].
+    FlushStart,
+    FlushStop,
+    /// The format of the data that follows has changed.
+    CapsChanged(Caps),
+    /// No more data will follow. Elements should send out what they have buffered.
+    Eos,
+    /// Application specific event.
+    Custom(Structure),
+    Seek(Seek),
+}
@@ -0,0 +1,330 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::element_traits::{
+    property::{self, PropertySpec, PropertyValue},
+    Element, ElementArchitecture, PadPresence, PadTemplate,
+};
+
+use super::{error::Error, state::State};
+
+/// Identifies an element that has been added to a [`PipelineGraph`].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct NodeId(pub(crate) usize);
+
+/// A pad on an element, identified by the element and the name of the pad.
+pub type PadRef = (NodeId, String);
+
+/// A link from a src pad to a sink pad.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Edge {
+    pub src: PadRef,
+    pub sink: PadRef,
+    /// How many datagrams can be in flight on the link. With 0 the src element waits until
+    /// the sink element has received each datagram.
+    pub capacity: usize,
+}
+
+/// Elements and the links between them.
+///
+/// # Example
+/// ```
+///use streamcraft::{
+///    elements::{conversion::bytes2text::Bytes2Text, text::stdoutlog::StdoutLog},
+///    elements::io::filesrc::FileSrc,
+///    pipeline::{graph::PipelineGraph, Pipeline},
+///};
+///
+///let mut graph = PipelineGraph::new();
+///let filesrc = graph.add_element(FileSrc::new(std::fs::File::open("README.md").unwrap()));
+///let bytes2text = graph.add_element(Bytes2Text::new());
+///let stdoutlog = graph.add_element(StdoutLog::new());
+///graph.link((filesrc, "src"), (bytes2text, "sink")).unwrap();
+///graph.link((bytes2text, "src"), (stdoutlog, "sink")).unwrap();
+///
+///let mut pipeline = Pipeline::new(graph);
+///pipeline.init().unwrap();
+///while pipeline.iter().is_ok() {}
+/// ```
+#[derive(Default)]
+pub struct PipelineGraph {
+    elements: Vec<Box<dyn Element>>,
+    edges: Vec<Edge>,
+}
+
+impl PipelineGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an element to the graph and get the id it can be linked with.
+    pub fn add_element(&mut self, element: impl Element + 'static) -> NodeId {
+        self.elements.push(Box::new(element));
+        NodeId(self.elements.len() - 1)
+    }
+
+    /// Like [`PipelineGraph::add_element`], for elements that are already boxed, e.g. from
+    /// [`make_element`](crate::registry::make_element).
+    pub fn add_boxed_element(&mut self, element: Box<dyn Element>) -> NodeId {
+        self.elements.push(element);
+        NodeId(self.elements.len() - 1)
+    }
+
+    fn element(&self, node: NodeId) -> Result<&dyn Element, Error> {
+        self.elements
+            .get(node.0)
+            .map(|element| element.as_ref())
+            .ok_or(Error::NoSuchNode)
+    }
+
+    fn element_mut(&mut self, node: NodeId) -> Result<&mut dyn Element, Error> {
+        match self.elements.get_mut(node.0) {
+            Some(element) => Ok(element.as_mut()),
+            None => Err(Error::NoSuchNode),
+        }
+    }
+
+    /// The pad templates of the element `node`.
+    pub fn architecture(&self, node: NodeId) -> Result<ElementArchitecture, Error> {
+        Ok(self.element(node)?.get_architecture())
+    }
+
+    /// Describe the properties of the element `node`.
+    pub fn properties(&self, node: NodeId) -> Result<Vec<PropertySpec>, Error> {
+        Ok(self.element(node)?.properties())
+    }
+
+    /// Get the property `name` of the element `node`.
+    pub fn property(&self, node: NodeId, name: &str) -> Result<PropertyValue, Error> {
+        property::get_property(self.element(node)?, name)
+    }
+
+    /// Set the property `name` of the element `node`.
+    pub fn set_property(
+        &mut self,
+        node: NodeId,
+        name: &str,
+        value: impl Into<PropertyValue>,
+    ) -> Result<(), Error> {
+        property::set_property(self.element_mut(node)?, State::Null, name, value.into())
+    }
+
+    /// Link the src pad `src` to the sink pad `sink`.
+    ///
+    /// Linking the name of a [`PadPresence::Request`] template, e.g. `src_%u`, creates a new
+    /// pad with the lowest free number.
+    pub fn link(&mut self, src: (NodeId, &str), sink: (NodeId, &str)) -> Result<(), Error> {
+        self.link_with_capacity(src, sink, 0)
+    }
+
+    /// Like [`PipelineGraph::link`], but `capacity` datagrams can be in flight on the link
+    /// before the src element has to wait for the sink element.
+    pub fn link_with_capacity(
+        &mut self,
+        src: (NodeId, &str),
+        sink: (NodeId, &str),
+        capacity: usize,
+    ) -> Result<(), Error> {
+        let src_element = self.element(src.0)?;
+        let src_architecture = src_element.get_architecture();
+        let src_template = src_architecture
+            .src_template(src.1)
+            .ok_or(Error::NoSuchPad)?;
+        let src_name = self.resolve_pad_name(src, src_element, src_template, |edge| &edge.src)?;
+
+        let sink_element = self.element(sink.0)?;
+        let sink_architecture = sink_element.get_architecture();
+        let sink_template = sink_architecture
+            .sink_template(sink.1)
+            .ok_or(Error::NoSuchPad)?;
+        let sink_name =
+            self.resolve_pad_name(sink, sink_element, sink_template, |edge| &edge.sink)?;
+
+        if !src_template.is_compatible_with(sink_template) {
+            return Err(Error::NoCommonFormat {
+                src: pad_path(src_element, src.0, &src_name),
+                sink: pad_path(sink_element, sink.0, &sink_name),
+                src_caps: src_template.caps.clone(),
+                sink_caps: sink_template.caps.clone(),
+            });
+        }
+
+        if self.is_reachable(sink.0, src.0) {
+            return Err(Error::LinkCreatesCycle);
+        }
+
+        self.edges.push(Edge {
+            src: (src.0, src_name),
+            sink: (sink.0, sink_name),
+            capacity,
+        });
+
+        Ok(())
+    }
+
+    /// Get the name of the pad that `pad` refers to and make sure it is not linked already.
+    fn resolve_pad_name(
+        &self,
+        (node, name): (NodeId, &str),
+        element: &dyn Element,
+        template: &PadTemplate,
+        side: impl Fn(&Edge) -> &PadRef,
+    ) -> Result<String, Error> {
+        let is_linked = |name: &str| {
+            self.edges
+                .iter()
+                .any(|edge| *side(edge) == (node, name.to_string()))
+        };
+
+        let name = match template.presence {
+            PadPresence::Always if template.is_pattern() => return Err(Error::NoSuchPad),
+            PadPresence::Always => name.to_string(),
+            PadPresence::Sometimes if element.has_pad(name) => name.to_string(),
+            PadPresence::Sometimes => return Err(Error::NoSuchPad),
+            PadPresence::Request if name == template.name => (0..)
+                .map(|n| template.pad_name(n))
+                .find(|name| !is_linked(name))
+                .ok_or(Error::NoSuchPad)?,
+            PadPresence::Request => name.to_string(),
+        };
+
+        if is_linked(&name) {
+            return Err(Error::PadAlreadyLinked);
+        }
+
+        Ok(name)
+    }
+
+    fn is_reachable(&self, from: NodeId, to: NodeId) -> bool {
+        let mut stack = vec![from];
+        let mut visited = vec![false; self.elements.len()];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if std::mem::replace(&mut visited[node.0], true) {
+                continue;
+            }
+            stack.extend(
+                self.edges
+                    .iter()
+                    .filter(|edge| edge.src.0 == node)
+                    .map(|edge| edge.sink.0),
+            );
+        }
+
+        false
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Get the nodes in an order where every node comes after all nodes linked to its sink pads.
+    pub(crate) fn topological_order(&self, node_count: usize) -> Vec<NodeId> {
+        let mut in_degree = vec![0; node_count];
+        for edge in &self.edges {
+            in_degree[(edge.sink.0).0] += 1;
+        }
+
+        let mut ready = (0..node_count)
+            .filter(|i| in_degree[*i] == 0)
+            .map(NodeId)
+            .collect::<Vec<NodeId>>();
+        let mut order = Vec::with_capacity(node_count);
+        while let Some(node) = ready.pop() {
+            order.push(node);
+            for edge in self.edges.iter().filter(|edge| edge.src.0 == node) {
+                let sink = (edge.sink.0).0;
+                in_degree[sink] -= 1;
+                if in_degree[sink] == 0 {
+                    ready.push(NodeId(sink));
+                }
+            }
+        }
+
+        order
+    }
+
+    pub(crate) fn take_elements(&mut self) -> Vec<Box<dyn Element>> {
+        std::mem::take(&mut self.elements)
+    }
+}
+
+/// Human readable path to an element, e.g. `filesrc0`.
+pub(crate) fn element_path(element: &dyn Element, node: NodeId) -> String {
+    format!("{}{}", element.name(), node.0)
+}
+
+/// Human readable path to a pad, e.g. `filesrc0:src`.
+pub(crate) fn pad_path(element: &dyn Element, node: NodeId, pad: &str) -> String {
+    format!("{}:{pad}", element_path(element, node))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        caps,
+        element_traits::{PadPresence, PadTemplate},
+        elements::misc::{testsink::TestSink, testsrc::TestSrc},
+    };
+
+    use super::*;
+
+    fn text_sink() -> TestSink {
+        TestSink::new(caps::text_utf8(), |_, _| true, |_, _| true)
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(caps::text_utf8(), Vec::new()));
+        let sink = graph.add_element(text_sink());
+        let bytes_sink = graph.add_element(TestSink::new(caps::bytes(), |_, _| true, |_, _| true));
+
+        assert!(matches!(
+            graph.link((src, "video"), (sink, "sink")),
+            Err(Error::NoSuchPad)
+        ));
+        assert!(matches!(
+            graph.link((src, "src"), (NodeId(42), "sink")),
+            Err(Error::NoSuchNode)
+        ));
+        assert!(matches!(
+            graph.link((src, "src"), (bytes_sink, "sink")),
+            Err(Error::NoCommonFormat { .. })
+        ));
+        graph.link((src, "src"), (sink, "sink")).unwrap();
+        assert!(matches!(
+            graph.link((src, "src"), (sink, "sink")),
+            Err(Error::PadAlreadyLinked)
+        ));
+    }
+
+    #[test]
+    fn test_pad_template_names() {
+        let template = PadTemplate::new("src_%u", PadPresence::Request, caps::text_utf8());
+        assert_eq!(template.pad_name(3), "src_3");
+        assert!(template.matches("src_3"));
+        assert!(template.matches("src_42"));
+        assert!(!template.matches("src_"));
+        assert!(!template.matches("src_x"));
+        assert!(!template.matches("sink_0"));
+
+        let template = PadTemplate::always("src", caps::text_utf8());
+        assert!(template.matches("src"));
+        assert!(!template.matches("src_0"));
+    }
+}
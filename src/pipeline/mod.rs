@@ -13,58 +13,189 @@
 // You should have received a copy of the GNU General Public License
 // along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{default, thread::JoinHandle};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
 
-use crate::{debug, define_log_info, element_traits::Element, error};
+use crate::{
+    caps::{Caps, Structure},
+    debug, define_log_info,
+    element_traits::{
+        compare_pad_names,
+        property::{self, PropertySpec, PropertyValue},
+        Element, PadDirection, PadPresence,
+    },
+    error,
+};
 
-use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use crossbeam_channel::{
+    bounded, unbounded, Receiver, Select, SendError, Sender, TryRecvError, TrySendError,
+};
 
+pub mod audio;
+pub mod bus;
 pub mod error;
+pub mod event;
+pub mod graph;
+pub mod parse;
+pub mod query;
+pub mod state;
+pub mod stats;
+pub mod video;
 
+use audio::AudioBuffer;
+use bus::{Bus, BusMessage, BusSender};
 use error::Error;
+use event::{Event, Seek, SeekFlags};
+use graph::{element_path, pad_path, Edge, NodeId, PipelineGraph};
+use query::{Format, Query};
+use state::{State, StateChange};
+use stats::{LinkCounter, LinkStats};
+use video::VideoFrame;
 
 // TODO: Only include when `av` feature is enabled
 use libav::demuxing::Packet;
 
-#[derive(PartialEq, Debug, Clone)]
+/// Not [`Clone`] since cloning a packet can fail, see [`Data::try_clone`].
+#[derive(PartialEq, Debug)]
 pub enum Data {
     Text(String),
     Bytes(Vec<u8>),
     AVPacket(Packet), // TODO: Only include when `av` feature is enabled
+    VideoFrame(VideoFrame),
+    AudioBuffer(AudioBuffer),
     None,
 }
 
+impl Data {
+    /// Size of the payload in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Bytes(bytes) => bytes.len(),
+            Self::AVPacket(packet) => packet.size(),
+            Self::VideoFrame(frame) => frame.size(),
+            Self::AudioBuffer(buffer) => buffer.size(),
+            Self::None => 0,
+        }
+    }
+
+    /// Clone the data, fails if libav can't allocate a new packet.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        Ok(match self {
+            Self::Text(text) => Self::Text(text.clone()),
+            Self::Bytes(bytes) => Self::Bytes(bytes.clone()),
+            Self::AVPacket(packet) => Self::AVPacket(packet.try_clone().map_err(Error::AVError)?),
+            Self::VideoFrame(frame) => Self::VideoFrame(frame.clone()),
+            Self::AudioBuffer(buffer) => Self::AudioBuffer(buffer.clone()),
+            Self::None => Self::None,
+        })
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Message {
     Iter,
     IterFin,
     Quit,
     Finished,
+    StateChange(StateChange),
+    StateChanged(State),
+    /// Sources produce on their own while playing instead of waiting for [`Message::Iter`].
+    FreeRun,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug)]
 pub enum Datagram {
     Message(Message),
     Data(Data),
+    Event(Event),
 }
 
-#[derive(Default)]
+impl Datagram {
+    /// Clone the datagram, see [`Data::try_clone`].
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        Ok(match self {
+            Self::Message(message) => Self::Message(message.clone()),
+            Self::Data(data) => Self::Data(data.try_clone()?),
+            Self::Event(event) => Self::Event(event.clone()),
+        })
+    }
+}
+
+/// Messages to the pipeline from one of its elements.
+#[derive(Clone)]
 pub struct Parent {
-    msg_sender: Option<Sender<Message>>,
+    node: NodeId,
+    path: String,
+    msg_sender: Sender<(NodeId, Message)>,
+    bus_sender: BusSender,
 }
 
 impl Parent {
-    pub fn new(msg_sender: Sender<Message>) -> Self {
+    pub fn new(
+        node: NodeId,
+        path: String,
+        msg_sender: Sender<(NodeId, Message)>,
+        bus_sender: BusSender,
+    ) -> Self {
         Self {
-            msg_sender: Some(msg_sender),
+            node,
+            path,
+            msg_sender,
+            bus_sender,
         }
     }
 
+    /// Post a message on the bus of the pipeline.
+    pub fn post(&self, msg: BusMessage) -> Result<(), Error> {
+        self.bus_sender.send(msg)
+    }
+
+    pub fn post_error(&self, error: Error) -> Result<(), Error> {
+        self.post(BusMessage::Error {
+            source: self.path.clone(),
+            error: Arc::new(error),
+        })
+    }
+
+    pub fn post_warning(&self, message: &str) -> Result<(), Error> {
+        self.post(BusMessage::Warning {
+            source: self.path.clone(),
+            message: message.to_string(),
+        })
+    }
+
+    pub fn post_info(&self, message: &str) -> Result<(), Error> {
+        self.post(BusMessage::Info {
+            source: self.path.clone(),
+            message: message.to_string(),
+        })
+    }
+
+    pub fn post_progress(&self, percent: u8) -> Result<(), Error> {
+        self.post(BusMessage::Progress {
+            source: self.path.clone(),
+            percent: percent.min(100),
+        })
+    }
+
+    pub fn post_application(&self, structure: Structure) -> Result<(), Error> {
+        self.post(BusMessage::Application {
+            source: self.path.clone(),
+            structure,
+        })
+    }
+
     fn send_msg(&self, msg: Message) -> Result<(), Error> {
-        match self.msg_sender.as_ref() {
-            Some(msg_sender) => msg_sender.send(msg).map_err(|_| Error::MessageParentFailed),
-            None => Err(Error::NoParentMessageSender),
-        }
+        self.msg_sender
+            .send((self.node, msg))
+            .map_err(|_| Error::MessageParentFailed)
     }
 
     pub fn send_finished(&self) -> Result<(), Error> {
@@ -74,29 +205,22 @@ impl Parent {
     pub fn send_iter_fin(&self) -> Result<(), Error> {
         self.send_msg(Message::IterFin)
     }
+
+    pub fn send_state_changed(&self, state: State) -> Result<(), Error> {
+        self.send_msg(Message::StateChanged(state))
+    }
 }
 
+/// The sending end of a link from a src pad to the sink pad of another element.
 #[derive(Default)]
 pub struct SinkPipe {
-    element: Option<Box<dyn Element>>,
-    pub thread_handle: Option<JoinHandle<()>>,
     pub datagram_sender: Option<Sender<Datagram>>,
-    pub msg_receiver: Option<Receiver<Message>>,
-    is_operational: bool,
 }
 
 impl SinkPipe {
-    pub fn set_element(&mut self, element: impl Element + 'static) {
-        self.element = Some(Box::new(element));
-    }
-
-    pub fn take_element(&mut self) -> Result<Box<dyn Element>, Error> {
-        match self.element.take() {
-            Some(elem) => {
-                self.is_operational = true;
-                Ok(elem)
-            }
-            None => Err(Error::NoSinkElement),
+    pub fn new(datagram_sender: Sender<Datagram>) -> Self {
+        Self {
+            datagram_sender: Some(datagram_sender),
         }
     }
 
@@ -109,114 +233,1026 @@ impl SinkPipe {
         }
     }
 
-    pub fn join_thread(&mut self) -> Result<(), Error> {
-        match self.thread_handle.take() {
-            Some(join_handle) => join_handle.join().map_err(|_| Error::FailedToJoinThread),
-            None => Err(Error::NoThreadHandle),
+    pub fn drop_data_sender(&mut self) {
+        self.datagram_sender.take();
+    }
+
+    pub fn send_datagram(&self, datagram: Datagram) -> Result<(), Error> {
+        match &self.datagram_sender {
+            Some(datagram_sender) => datagram_sender.send(datagram).map_err(Error::SendError),
+            None => Err(Error::NoSinkDatagramSender),
         }
     }
 
-    pub fn drop_data_sender(&mut self) {
-        self.datagram_sender.take();
+    /// Like [`SinkPipe::send_datagram`], but returns `Ok(false)` instead of waiting if the sink
+    /// element is not ready to receive the datagram.
+    pub fn try_send_datagram(&self, datagram: Datagram) -> Result<bool, Error> {
+        match &self.datagram_sender {
+            Some(datagram_sender) => match datagram_sender.try_send(datagram) {
+                Ok(()) => Ok(true),
+                Err(TrySendError::Full(_)) => Ok(false),
+                Err(TrySendError::Disconnected(datagram)) => {
+                    Err(Error::SendError(SendError(datagram)))
+                }
+            },
+            None => Err(Error::NoSinkDatagramSender),
+        }
+    }
+
+    /// Whether the src pad is linked to a sink element.
+    pub fn is_linked(&self) -> bool {
+        self.datagram_sender.is_some()
     }
+}
+
+/// Something an element received from the pipeline or from one of its sink pads.
+#[derive(Debug)]
+pub enum Input {
+    /// Message from the pipeline, e.g. [`Message::Iter`] for sources.
+    Control(Message),
+    /// Datagram from the upstream element linked to the sink pad at the index. The name of the
+    /// pad can be looked up with [`ElementContext::sink_name`].
+    Sink(usize, Datagram),
+}
+
+/// A query or upstream event, with the channel the answer is sent on.
+pub(crate) enum Request {
+    /// Answered with the query, or `None` if nobody answered it.
+    Query(Query, Sender<Option<Query>>),
+    /// Answered with whether the event was handled.
+    Event(Event, Sender<bool>),
+}
+
+/// A [`Request`] on its way to the pad `pad`.
+pub(crate) struct PadRequest {
+    direction: PadDirection,
+    pad: String,
+    request: Request,
+}
+
+/// A function that is called with an element and the state it is in.
+type ElementCall = Box<dyn FnOnce(&mut dyn Element, State) + Send>;
 
-    pub fn try_recv_msg(&self) -> Result<Option<Message>, Error> {
-        match &self.msg_receiver {
-            Some(receiver) => match receiver.try_recv() {
-                Ok(msg) => Ok(Some(msg)),
-                Err(e) if e.is_empty() => Ok(None),
-                Err(_e) => Err(Error::ReceiveFromSinkFailed),
+/// Something an element is asked to do in its thread.
+pub(crate) enum ElementRequest {
+    Pad(PadRequest),
+    Call(ElementCall),
+}
+
+/// The pad on the other side of a link.
+struct Peer {
+    requests: Sender<ElementRequest>,
+    pad: String,
+}
+
+enum Selected {
+    Input(Input),
+    Request(ElementRequest),
+}
+
+fn opposite(direction: PadDirection) -> PadDirection {
+    match direction {
+        PadDirection::Src => PadDirection::Sink,
+        PadDirection::Sink => PadDirection::Src,
+    }
+}
+
+/// Everything an element needs to talk to the pipeline and its linked neighbours.
+pub struct ElementContext {
+    pub parent: Parent,
+    state: State,
+    free_running: bool,
+    /// Whether the pipeline is waiting for [`ElementContext::iter_fin`].
+    iterating: bool,
+    control: Receiver<Message>,
+    requests: Receiver<ElementRequest>,
+    /// Where to send requests that leave through a pad, by the direction and name of the pad.
+    peers: Vec<(PadDirection, String, Peer)>,
+    sinks: Vec<(String, Receiver<Datagram>)>,
+    /// Counts the data received on each sink pad, in the same order as `sinks`.
+    sink_counters: Vec<Arc<LinkCounter>>,
+    /// Indices of the sink pads that received [`Message::Quit`] and are not listened to any more.
+    closed_sinks: Vec<usize>,
+    srcs: Vec<(String, SinkPipe)>,
+}
+
+impl ElementContext {
+    /// Block until a message arrives from the pipeline or a datagram arrives on any sink pad.
+    ///
+    /// State changes from the pipeline, queries and upstream events are handled here by calling
+    /// the methods of `element`, which should be the element that is running.
+    ///
+    /// A free running source gets [`Message::Iter`] whenever it is playing and there is
+    /// nothing else to handle. Until it is playing it blocks like every other element.
+    pub fn recv(&mut self, element: &mut dyn Element) -> Result<Input, Error> {
+        loop {
+            let input = if self.free_running && self.state == State::Playing {
+                if let Ok(request) = self.requests.try_recv() {
+                    self.handle_request(element, request);
+                    continue;
+                }
+                match self.control.try_recv() {
+                    Ok(msg) => Input::Control(msg),
+                    Err(TryRecvError::Empty) => return Ok(Input::Control(Message::Iter)),
+                    Err(TryRecvError::Disconnected) => return Err(Error::FailedToRecvFromParent),
+                }
+            } else {
+                match self.select()? {
+                    Selected::Input(input) => input,
+                    Selected::Request(request) => {
+                        self.handle_request(element, request);
+                        continue;
+                    }
+                }
+            };
+
+            match input {
+                Input::Control(Message::StateChange(change)) => {
+                    element.change_state(change)?;
+                    self.state = change.next();
+                    self.parent.send_state_changed(self.state)?;
+                }
+                Input::Control(Message::FreeRun) => self.free_running = true,
+                Input::Control(Message::Iter) => {
+                    self.iterating = true;
+                    return Ok(Input::Control(Message::Iter));
+                }
+                Input::Sink(index, Datagram::Message(Message::Quit)) => {
+                    // The upstream element hangs up after this
+                    self.closed_sinks.push(index);
+                    return Ok(Input::Sink(index, Datagram::Message(Message::Quit)));
+                }
+                input => return Ok(input),
+            }
+        }
+    }
+
+    fn select(&self) -> Result<Selected, Error> {
+        let mut select = Select::new();
+        select.recv(&self.control);
+        select.recv(&self.requests);
+        let open_sinks = (0..self.sinks.len())
+            .filter(|index| !self.closed_sinks.contains(index))
+            .collect::<Vec<usize>>();
+        for index in &open_sinks {
+            select.recv(&self.sinks[*index].1);
+        }
+
+        let op = select.select();
+        match op.index() {
+            0 => op
+                .recv(&self.control)
+                .map(|msg| Selected::Input(Input::Control(msg)))
+                .map_err(|_| Error::FailedToRecvFromParent),
+            1 => op
+                .recv(&self.requests)
+                .map(Selected::Request)
+                .map_err(|_| Error::FailedToRecvFromParent),
+            i => {
+                let index = open_sinks[i - 2];
+                let datagram = op
+                    .recv(&self.sinks[index].1)
+                    .map_err(|_| Error::FailedToRecvFromParent)?;
+                if let Datagram::Data(data) = &datagram {
+                    self.sink_counters[index].count(data);
+                }
+                Ok(Selected::Input(Input::Sink(index, datagram)))
+            }
+        }
+    }
+
+    /// Let `element` handle the request, or pass it on through the linked pads on the other side
+    /// of the element. Queries are passed on through one pad only, see
+    /// [`Element::query_forward_pad`].
+    fn handle_request(&mut self, element: &mut dyn Element, request: ElementRequest) {
+        match request {
+            ElementRequest::Pad(pad_request) => self.handle_pad_request(element, pad_request),
+            ElementRequest::Call(call) => call(element, self.state),
+        }
+    }
+
+    /// Handle the queries and upstream events that are waiting, without blocking. Elements that
+    /// wait for something other than [`ElementContext::recv`] should call this now and then.
+    pub fn handle_pending_requests(&mut self, element: &mut dyn Element) {
+        while let Ok(request) = self.requests.try_recv() {
+            self.handle_request(element, request);
+        }
+    }
+
+    fn handle_pad_request(&mut self, element: &mut dyn Element, mut pad_request: PadRequest) {
+        let (direction, pad) = (pad_request.direction, pad_request.pad.as_str());
+        let handled = match &mut pad_request.request {
+            Request::Query(query, _) => {
+                element.query(direction, pad, query)
+                    || match query {
+                        Query::Caps { filter, caps } if !pad.is_empty() => {
+                            *caps = element.query_caps(direction, pad).intersect(filter);
+                            true
+                        }
+                        _ => false,
+                    }
+            }
+            Request::Event(event, _) => match element.upstream_event(self, pad, event) {
+                Ok(handled) => handled,
+                Err(e) => {
+                    error!("Failed to handle upstream event: {e}");
+                    let _ = self.parent.post_error(e);
+                    false
+                }
             },
-            None => Err(Error::NoSinkMessageReceiver),
+        };
+
+        let mut forward_to = self
+            .peers
+            .iter()
+            .filter(|(dir, _, _)| *dir == opposite(direction))
+            .collect::<Vec<_>>();
+        if let Request::Query(..) = pad_request.request {
+            let query_pad = element.query_forward_pad(direction, pad);
+            forward_to = forward_to
+                .into_iter()
+                .filter(|(_, name, _)| query_pad.as_ref().is_none_or(|pad| pad == name))
+                .min_by(|(_, a, _), (_, b, _)| compare_pad_names(a, b))
+                .into_iter()
+                .collect();
+        }
+        match pad_request.request {
+            Request::Query(query, reply) if handled => {
+                let _ = reply.send(Some(query));
+            }
+            Request::Event(_, reply) if handled => {
+                let _ = reply.send(true);
+            }
+            // Dropping the reply channels of requests that can not be passed on answers them
+            request => {
+                for (_, _, peer) in forward_to {
+                    let request = match &request {
+                        Request::Query(query, reply) => {
+                            Request::Query(query.clone(), reply.clone())
+                        }
+                        Request::Event(event, reply) => {
+                            Request::Event(event.clone(), reply.clone())
+                        }
+                    };
+                    // Fails if the peer has finished
+                    let _ = peer.requests.send(ElementRequest::Pad(PadRequest {
+                        // The pad on the other side has the opposite direction of ours
+                        direction: pad_request.direction,
+                        pad: peer.pad.clone(),
+                        request,
+                    }));
+                }
+            }
         }
     }
 
-    pub fn send_datagram(&mut self, datagram: Datagram) -> Result<(), Error> {
-        match &self.datagram_sender {
-            Some(datagram_sender) => datagram_sender
-                .send(datagram)
-                .map_err(|e| Error::SendError(e))?,
-            None => return Err(Error::NoSinkDatagramSender),
+    /// Send `query` to the peer of the pad named `pad`. The answer, or `None` if nobody could
+    /// answer, arrives on the returned channel.
+    ///
+    /// Blocking on the answer can dead lock if the peer is waiting for this element to accept
+    /// data.
+    pub fn query_peer(
+        &self,
+        direction: PadDirection,
+        pad: &str,
+        query: Query,
+    ) -> Result<Receiver<Option<Query>>, Error> {
+        let (_, _, peer) = self
+            .peers
+            .iter()
+            .find(|(dir, name, _)| *dir == direction && name == pad)
+            .ok_or(Error::NoSuchPad)?;
+        let (reply, answer) = bounded(1);
+        peer.requests
+            .send(ElementRequest::Pad(PadRequest {
+                direction: opposite(direction),
+                pad: peer.pad.clone(),
+                request: Request::Query(query, reply),
+            }))
+            .map_err(|_| Error::FailedToSendQuery)?;
+
+        Ok(answer)
+    }
+
+    /// Tell the pipeline that the source is done with the current [`Message::Iter`].
+    pub fn iter_fin(&mut self) -> Result<(), Error> {
+        if std::mem::take(&mut self.iterating) {
+            self.parent.send_iter_fin()?;
         }
 
         Ok(())
     }
 
-    pub fn is_operational(&self) -> bool {
-        self.is_operational
+    /// The state the element is in.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Get the name of the sink pad at `index`.
+    pub fn sink_name(&self, index: usize) -> Option<&str> {
+        self.sinks.get(index).map(|(name, _)| name.as_str())
+    }
+
+    /// Names of all sink pads, in the order their indices refer to.
+    pub fn sink_names(&self) -> impl Iterator<Item = &str> {
+        self.sinks.iter().map(|(name, _)| name.as_str())
     }
+
+    /// Get the src pad named `name`.
+    pub fn src(&self, name: &str) -> Result<&SinkPipe, Error> {
+        self.srcs
+            .iter()
+            .find(|(src_name, _)| src_name == name)
+            .map(|(_, src)| src)
+            .ok_or(Error::NoSuchPad)
+    }
+
+    /// Names of all src pads. Pads that are not linked are included.
+    pub fn src_names(&self) -> impl Iterator<Item = &str> {
+        self.srcs.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Send `event` on every linked src pad.
+    pub fn push_event(&self, event: Event) -> Result<(), Error> {
+        for (name, src) in self.srcs.iter().filter(|(_, src)| src.is_linked()) {
+            src.send_datagram(Datagram::Event(event.clone()))
+                .map_err(|e| self.pad_error(name, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a datagram on the src pad named `name`.
+    pub fn push(&self, name: &str, datagram: Datagram) -> Result<(), Error> {
+        self.src(name)?
+            .send_datagram(datagram)
+            .map_err(|e| self.pad_error(name, e))
+    }
+
+    /// Wrap `error`, which happened on the pad named `pad`, with the path of the pad.
+    pub fn pad_error(&self, pad: &str, error: Error) -> Error {
+        Error::Pad {
+            path: format!("{}:{pad}", self.parent.path),
+            error: Box::new(error),
+        }
+    }
+
+    /// Tell every linked sink element to quit and hang up on them.
+    fn quit_srcs(&mut self) {
+        for (_, src) in self.srcs.iter_mut().filter(|(_, src)| src.is_linked()) {
+            if let Err(e) = src.send_quit() {
+                debug!("Failed to send quit to sink: {e}");
+            }
+            src.drop_data_sender();
+        }
+    }
+}
+
+/// What the thread of an element returns when it is joined.
+type ElementResult = (Box<dyn Element>, Result<(), Arc<Error>>);
+
+struct PipelineNode {
+    element: Option<Box<dyn Element>>,
+    /// E.g. `filesrc0`.
+    path: String,
+    is_src: bool,
+    control: Option<Sender<Message>>,
+    requests: Option<Sender<ElementRequest>>,
+    thread_handle: Option<JoinHandle<ElementResult>>,
+    finished: bool,
+    /// What the element failed with the last time it ran.
+    error: Option<Arc<Error>>,
 }
 
+/// Runs the elements of a [`PipelineGraph`], one thread per element.
+///
+/// The pipeline starts in [`State::Null`] and is moved between states with
+/// [`Pipeline::set_state`]. Threads are spawned when going to [`State::Paused`] and joined when
+/// going back to [`State::Ready`], after which the pipeline can be started again.
+///
+/// Sources produce data either once per [`Pipeline::iter`], or on their own as fast as
+/// downstream accepts it after [`Pipeline::play`].
 pub struct Pipeline {
-    head: SinkPipe,
+    nodes: Vec<PipelineNode>,
+    graph: PipelineGraph,
+    state: State,
+    free_running: bool,
+    bus: Bus,
+    bus_sender: BusSender,
+    negotiated: Vec<(Edge, Caps)>,
+    link_counters: Vec<(Edge, Arc<LinkCounter>)>,
+    msg_receiver: Option<Receiver<(NodeId, Message)>>,
 }
 
 impl Pipeline {
-    pub fn new(element: impl Element + 'static) -> Self {
-        let mut head = SinkPipe::default();
-        head.set_element(element);
-        Self { head }
+    pub fn new(mut graph: PipelineGraph) -> Self {
+        let nodes = graph
+            .take_elements()
+            .into_iter()
+            .enumerate()
+            .map(|(i, element)| PipelineNode {
+                is_src: element.get_architecture().is_src(),
+                path: element_path(element.as_ref(), NodeId(i)),
+                element: Some(element),
+                control: None,
+                requests: None,
+                thread_handle: None,
+                finished: false,
+                error: None,
+            })
+            .collect();
+        let (bus_sender, bus) = bus::channel();
+
+        Self {
+            nodes,
+            graph,
+            state: State::Null,
+            free_running: false,
+            bus,
+            bus_sender,
+            negotiated: Vec::new(),
+            link_counters: Vec::new(),
+            msg_receiver: None,
+        }
     }
 
+    fn element(&self, node: NodeId) -> Result<&dyn Element, Error> {
+        self.nodes
+            .get(node.0)
+            .and_then(|node| node.element.as_deref())
+            .ok_or(Error::NoSinkElement)
+    }
+
+    fn element_mut(&mut self, node: NodeId) -> Result<&mut dyn Element, Error> {
+        match self.nodes.get_mut(node.0) {
+            Some(PipelineNode {
+                element: Some(element),
+                ..
+            }) => Ok(element.as_mut()),
+            _ => Err(Error::NoSinkElement),
+        }
+    }
+
+    /// Fail if an always present sink pad is not linked, its element would wait for data forever.
+    fn check_sinks_linked(&self) -> Result<(), Error> {
+        let edges = self.graph.edges();
+        for i in 0..self.nodes.len() {
+            let node = NodeId(i);
+            for template in self.element(node)?.get_architecture().sinks {
+                if template.presence == PadPresence::Always
+                    && !edges
+                        .iter()
+                        .any(|edge| edge.sink.0 == node && edge.sink.1 == template.name)
+                {
+                    return Err(Error::UnlinkedSinkPad);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Agree on a format for every link. Goes through the elements from the sources and down so
+    /// every element knows the caps of its sink pads before it is asked what it can produce.
+    fn negotiate(&mut self) -> Result<(), Error> {
+        let edges = self.graph.edges().to_vec();
+        let mut negotiated = Vec::with_capacity(edges.len());
+        for node in self.graph.topological_order(self.nodes.len()) {
+            for edge in edges.iter().filter(|edge| edge.src.0 == node) {
+                let (src_node, src_pad) = (edge.src.0, edge.src.1.as_str());
+                let (sink_node, sink_pad) = (edge.sink.0, edge.sink.1.as_str());
+
+                let src_element = self.element(src_node)?;
+                let sink_element = self.element(sink_node)?;
+                let src_caps = src_element.query_caps(PadDirection::Src, src_pad);
+                let sink_caps = sink_element.query_caps(PadDirection::Sink, sink_pad);
+
+                let caps = src_caps.intersect(&sink_caps);
+                if caps.is_empty() {
+                    return Err(Error::NoCommonFormat {
+                        src: pad_path(src_element, src_node, src_pad),
+                        sink: pad_path(sink_element, sink_node, sink_pad),
+                        src_caps,
+                        sink_caps,
+                    });
+                }
+
+                let caps = caps.fixate();
+                self.element_mut(src_node)?
+                    .set_caps(PadDirection::Src, src_pad, &caps)?;
+                self.element_mut(sink_node)?
+                    .set_caps(PadDirection::Sink, sink_pad, &caps)?;
+                negotiated.push((edge.clone(), caps));
+            }
+        }
+        self.negotiated = negotiated;
+
+        Ok(())
+    }
+
+    /// The caps every link agreed on when the pipeline was initialized.
+    pub fn negotiated_caps(&self) -> &[(Edge, Caps)] {
+        &self.negotiated
+    }
+
+    /// How much data went over every link since the pipeline was last started.
+    pub fn link_stats(&self) -> Vec<(Edge, LinkStats)> {
+        self.link_counters
+            .iter()
+            .map(|(edge, counter)| (edge.clone(), counter.get()))
+            .collect()
+    }
+
+    /// Human readable name of the element `node`, e.g. `filesrc0`.
+    pub fn element_path(&self, node: NodeId) -> Option<&str> {
+        self.nodes.get(node.0).map(|node| node.path.as_str())
+    }
+
+    /// Set the pipeline to [`State::Playing`].
     pub fn init(&mut self) -> Result<(), Error> {
-        let (datagram_sender, datagram_receiver) = bounded(0);
-        let (msg_sender, my_msg_receiver) = unbounded();
-        let parent = Parent::new(msg_sender);
-        let mut sink_element = self.head.take_element()?;
-        sink_element.set_parent(parent);
-        let datagram_receiver_clone = datagram_receiver.clone();
-
-        self.head.thread_handle = Some(std::thread::spawn(move || {
-            match sink_element.run(datagram_receiver_clone) {
-                Ok(_) => {}
-                Err(e) => error!("PIPELINE: Error occurred running sink element: {e}"),
-            }
-        }));
-        self.head.msg_receiver = Some(my_msg_receiver);
-        self.head.datagram_sender = Some(datagram_sender);
+        if self.state != State::Null {
+            return Err(Error::PipelineAlreadyInitialized);
+        }
+
+        self.set_state(State::Playing)
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Get the bus the pipeline and its elements post messages on.
+    pub fn bus(&self) -> Bus {
+        self.bus.clone()
+    }
+
+    /// Set the pipeline to [`State::Playing`] and let the sources push data on their own until
+    /// they are done. See [`Pipeline::wait_eos`].
+    pub fn play(&mut self) -> Result<(), Error> {
+        if !self.free_running {
+            self.free_running = true;
+            for node in self.nodes.iter().filter(|node| node.is_src) {
+                if let Some(control) = &node.control {
+                    // Fails if the source already finished, which is fine
+                    let _ = control.send(Message::FreeRun);
+                }
+            }
+        }
+
+        self.set_state(State::Playing)
+    }
+
+    /// Block until every element has finished after [`Pipeline::play`]. Returns the error that
+    /// made the pipeline stop if an element failed, see [`Error::root_cause`].
+    pub fn wait_eos(&mut self) -> Result<(), Error> {
+        if !self.free_running {
+            return Err(Error::PipelineNotFreeRunning);
+        }
+        if self.state != State::Playing {
+            return Err(Error::PipelineNotPlaying);
+        }
+        let msg_receiver = self.msg_receiver.clone().ok_or(Error::PipelineNotReady)?;
+
+        while self.nodes.iter().any(|node| !node.finished) {
+            let (node, msg) = msg_receiver
+                .recv()
+                .map_err(|_| Error::ReceiveFromSinkFailed)?;
+            if msg == Message::Finished {
+                self.finish(node);
+            }
+        }
+
+        self.failure().map_or(Ok(()), Err)
+    }
+
+    /// Mark `node` as finished and join its thread, which is about to exit.
+    fn finish(&mut self, node: NodeId) {
+        let node = &mut self.nodes[node.0];
+        node.finished = true;
+        if let Some(join_handle) = node.thread_handle.take() {
+            match join_handle.join() {
+                Ok((element, result)) => {
+                    node.element = Some(element);
+                    node.error = result.err();
+                }
+                Err(_) => error!("{}", Error::FailedToJoinThread),
+            }
+        }
+    }
+
+    /// The error of the element that failed first for a reason of its own. Elements that only
+    /// failed because a linked element stopped are used if there is nothing else.
+    fn failure(&self) -> Option<Error> {
+        let mut failed = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.error.as_ref().map(|error| (node, error)));
+        let first = failed.clone().next();
+        let (node, error) = failed
+            .find(|(_, error)| !error.is_disconnected())
+            .or(first)?;
+
+        Some(Error::Element {
+            path: node.path.clone(),
+            error: Arc::clone(error),
+        })
+    }
+
+    /// Go through every state between the current state and `state`.
+    pub fn set_state(&mut self, state: State) -> Result<(), Error> {
+        while let Some(change) = StateChange::towards(self.state, state) {
+            self.change_state(change)?;
+            self.state = change.next();
+            debug!("State changed: {change}");
+            // Nobody listening on the bus is fine
+            let _ = self.bus_sender.send(BusMessage::StateChanged {
+                source: String::from("pipeline"),
+                old: change.current(),
+                new: change.next(),
+            });
+        }
 
         Ok(())
     }
 
-    pub fn iter(&self) -> Result<(), Error> {
-        if self.head.thread_handle.is_none() {
+    fn change_state(&mut self, change: StateChange) -> Result<(), Error> {
+        match change {
+            StateChange::NullToReady | StateChange::ReadyToNull => {
+                self.change_element_states(change)
+            }
+            StateChange::ReadyToPaused => {
+                self.check_sinks_linked()?;
+                self.negotiate()?;
+                self.change_element_states(change)?;
+                self.spawn()
+            }
+            StateChange::PausedToPlaying | StateChange::PlayingToPaused => {
+                self.change_thread_states(change)
+            }
+            StateChange::PausedToReady => {
+                self.join();
+                self.free_running = false;
+                self.change_element_states(change)
+            }
+        }
+    }
+
+    /// Change the state of the elements that are not running in a thread.
+    fn change_element_states(&mut self, change: StateChange) -> Result<(), Error> {
+        for node in self.nodes.iter_mut() {
+            node.element
+                .as_mut()
+                .ok_or(Error::NoSinkElement)?
+                .change_state(change)?;
+        }
+
+        Ok(())
+    }
+
+    /// Change the state of the elements running in threads and wait until they are done.
+    fn change_thread_states(&mut self, change: StateChange) -> Result<(), Error> {
+        let msg_receiver = self.msg_receiver.clone().ok_or(Error::PipelineNotReady)?;
+
+        let mut pending = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.finished {
+                continue;
+            }
+            if let Some(control) = &node.control {
+                if control.send(Message::StateChange(change)).is_ok() {
+                    pending.push(NodeId(i));
+                }
+            }
+        }
+
+        while !pending.is_empty() {
+            let (node, msg) = msg_receiver
+                .recv()
+                .map_err(|_| Error::ReceiveFromSinkFailed)?;
+            match msg {
+                Message::StateChanged(_) => {}
+                Message::Finished => self.finish(node),
+                _ => continue,
+            }
+            pending.retain(|n| *n != node);
+        }
+
+        Ok(())
+    }
+
+    fn spawn(&mut self) -> Result<(), Error> {
+        let (msg_sender, msg_receiver) = unbounded();
+        let running = Arc::new(AtomicUsize::new(self.nodes.len()));
+        let failed = Arc::new(AtomicBool::new(false));
+        let mut contexts = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter_mut().enumerate() {
+            let element = node.element.as_deref().ok_or(Error::NoSinkElement)?;
+            let architecture = element.get_architecture();
+            let (control_sender, control_receiver) = unbounded();
+            let (request_sender, request_receiver) = unbounded();
+            node.control = Some(control_sender);
+            node.requests = Some(request_sender);
+            node.finished = false;
+            node.error = None;
+            contexts.push(ElementContext {
+                parent: Parent::new(
+                    NodeId(i),
+                    node.path.clone(),
+                    msg_sender.clone(),
+                    self.bus_sender.clone(),
+                ),
+                state: State::Paused,
+                free_running: self.free_running && node.is_src,
+                iterating: false,
+                control: control_receiver,
+                requests: request_receiver,
+                peers: Vec::new(),
+                sinks: Vec::new(),
+                sink_counters: Vec::new(),
+                closed_sinks: Vec::new(),
+                // Unlinked always pads are still there, they just have nowhere to send to
+                srcs: architecture
+                    .srcs
+                    .iter()
+                    .filter(|template| template.presence == PadPresence::Always)
+                    .map(|template| (template.name.clone(), SinkPipe::default()))
+                    .collect(),
+            });
+        }
+
+        self.link_counters.clear();
+        for edge in self.graph.edges() {
+            let (datagram_sender, datagram_receiver) = bounded(edge.capacity);
+            let srcs = &mut contexts[(edge.src.0).0].srcs;
+            match srcs.iter_mut().find(|(name, _)| *name == edge.src.1) {
+                Some((_, src)) => *src = SinkPipe::new(datagram_sender),
+                None => srcs.push((edge.src.1.clone(), SinkPipe::new(datagram_sender))),
+            }
+            contexts[(edge.sink.0).0]
+                .sinks
+                .push((edge.sink.1.clone(), datagram_receiver));
+            let counter = Arc::new(LinkCounter::default());
+            contexts[(edge.sink.0).0]
+                .sink_counters
+                .push(Arc::clone(&counter));
+            self.link_counters.push((edge.clone(), counter));
+
+            let peer = |(node, pad): &(NodeId, String)| -> Result<Peer, Error> {
+                Ok(Peer {
+                    requests: self.nodes[node.0]
+                        .requests
+                        .clone()
+                        .ok_or(Error::PipelineNotReady)?,
+                    pad: pad.clone(),
+                })
+            };
+            contexts[(edge.src.0).0].peers.push((
+                PadDirection::Src,
+                edge.src.1.clone(),
+                peer(&edge.sink)?,
+            ));
+            contexts[(edge.sink.0).0].peers.push((
+                PadDirection::Sink,
+                edge.sink.1.clone(),
+                peer(&edge.src)?,
+            ));
+        }
+
+        for (i, (node, mut ctx)) in self.nodes.iter_mut().zip(contexts).enumerate() {
+            let mut element = node.element.take().ok_or(Error::NoSinkElement)?;
+            let running = Arc::clone(&running);
+            let failed = Arc::clone(&failed);
+            node.thread_handle = Some(std::thread::spawn(move || {
+                let result = element.run(&mut ctx).map_err(|e| {
+                    error!("Error occurred running element: {e}");
+                    failed.store(true, Ordering::SeqCst);
+                    let e = Arc::new(e);
+                    let _ = ctx.parent.post(BusMessage::Error {
+                        source: ctx.parent.path.clone(),
+                        error: Arc::clone(&e),
+                    });
+                    e
+                });
+                ctx.quit_srcs();
+                // Nobody listening on the bus is fine
+                let _ = ctx.parent.post(BusMessage::ElementFinished(NodeId(i)));
+                // The stream only ended if no element failed on the way
+                if running.fetch_sub(1, Ordering::SeqCst) == 1 && !failed.load(Ordering::SeqCst) {
+                    let _ = ctx.parent.post(BusMessage::Eos);
+                }
+                if let Err(e) = ctx.parent.send_finished() {
+                    debug!("{e}");
+                }
+                (element, result)
+            }));
+        }
+        self.msg_receiver = Some(msg_receiver);
+
+        Ok(())
+    }
+
+    /// Stop every thread and take the elements back.
+    fn join(&mut self) {
+        // Sources quit when told to, everything downstream quits when its sources have quit
+        for node in self.nodes.iter().filter(|node| node.is_src) {
+            if let Some(control) = &node.control {
+                // Fails if the source already finished, which is fine
+                let _ = control.send(Message::Quit);
+            }
+        }
+
+        // The channels are closed after joining so that no element sees them close while running
+        for node in self.nodes.iter_mut() {
+            if let Some(join_handle) = node.thread_handle.take() {
+                match join_handle.join() {
+                    Ok((element, result)) => {
+                        node.element = Some(element);
+                        node.error = result.err();
+                    }
+                    Err(_) => error!("{}", Error::FailedToJoinThread),
+                }
+            }
+            node.control = None;
+            node.requests = None;
+        }
+        self.msg_receiver = None;
+    }
+
+    /// Send a request to the elements at the downstream end of the pipeline, from where it
+    /// travels upstream.
+    fn send_upstream(&self, request: impl Fn() -> Request) -> Result<(), Error> {
+        if self.msg_receiver.is_none() {
             return Err(Error::PipelineNotReady);
         }
 
-        if let Some(datagram_sender) = &self.head.datagram_sender {
-            datagram_sender
-                .send(Datagram::Message(Message::Iter))
-                .map_err(|_| Error::MessageSinkFailed)?;
-        } else {
-            return Err(Error::NoSinkDatagramSender);
+        let edges = self.graph.edges();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if edges.iter().any(|edge| edge.src.0 == NodeId(i)) {
+                continue;
+            }
+            if let Some(requests) = &node.requests {
+                // Fails if the element has finished
+                let _ = requests.send(ElementRequest::Pad(PadRequest {
+                    direction: PadDirection::Src,
+                    pad: String::new(),
+                    request: request(),
+                }));
+            }
         }
 
-        if let Some(message_receiver) = &self.head.msg_receiver {
-            match message_receiver
+        Ok(())
+    }
+
+    /// Send `query` upstream from the elements at the downstream end of the pipeline until an
+    /// element answers it. Returns `None` if nobody answered.
+    pub fn query(&self, query: Query) -> Result<Option<Query>, Error> {
+        let (reply, answer) = unbounded();
+        self.send_upstream(|| Request::Query(query.clone(), reply.clone()))?;
+        drop(reply);
+
+        Ok(answer.iter().flatten().next())
+    }
+
+    /// Send `event` upstream from the elements at the downstream end of the pipeline and return
+    /// whether any element handled it.
+    pub fn send_event(&self, event: Event) -> Result<bool, Error> {
+        let (reply, answer) = unbounded();
+        self.send_upstream(|| Request::Event(event.clone(), reply.clone()))?;
+        drop(reply);
+
+        Ok(answer.iter().any(|handled| handled))
+    }
+
+    /// Continue playing from `position`. Returns whether any element could seek.
+    pub fn seek(&self, position: Duration, flags: SeekFlags) -> Result<bool, Error> {
+        self.send_event(Event::Seek(Seek::new(
+            Format::Time,
+            position.as_nanos() as u64,
+            flags,
+        )))
+    }
+
+    /// Continue playing from the byte offset `position`. Returns whether any element could seek.
+    pub fn seek_bytes(&self, position: u64, flags: SeekFlags) -> Result<bool, Error> {
+        self.send_event(Event::Seek(Seek::new(Format::Bytes, position, flags)))
+    }
+
+    /// Query the current position in `format`.
+    pub fn query_position(&self, format: Format) -> Result<Option<u64>, Error> {
+        Ok(match self.query(Query::position(format))? {
+            Some(Query::Position { position, .. }) => position,
+            _ => None,
+        })
+    }
+
+    /// Query the duration in `format`.
+    pub fn query_duration(&self, format: Format) -> Result<Option<u64>, Error> {
+        Ok(match self.query(Query::duration(format))? {
+            Some(Query::Duration { duration, .. }) => duration,
+            _ => None,
+        })
+    }
+
+    /// Call `f` with the element `node` and the state it is in. Elements that are running are
+    /// called in their thread, so this waits until the element handles the request.
+    fn with_element<T: Send + 'static>(
+        &mut self,
+        node: NodeId,
+        f: impl FnOnce(&mut dyn Element, State) -> T + Send + 'static,
+    ) -> Result<T, Error> {
+        let state = self.state;
+        let node = self.nodes.get_mut(node.0).ok_or(Error::NoSuchNode)?;
+        if let Some(element) = node.element.as_deref_mut() {
+            return Ok(f(element, state));
+        }
+
+        let requests = node.requests.as_ref().ok_or(Error::PipelineNotReady)?;
+        let (reply, answer) = bounded(1);
+        requests
+            .send(ElementRequest::Call(Box::new(move |element, state| {
+                let _ = reply.send(f(element, state));
+            })))
+            .map_err(|_| Error::ElementNotReady)?;
+        // The element dropped the request without handling it if it has finished
+        answer.recv().map_err(|_| Error::ElementNotReady)
+    }
+
+    /// Describe the properties of the element `node`.
+    pub fn properties(&mut self, node: NodeId) -> Result<Vec<PropertySpec>, Error> {
+        self.with_element(node, |element, _| element.properties())
+    }
+
+    /// Get the property `name` of the element `node`.
+    pub fn property(&mut self, node: NodeId, name: &str) -> Result<PropertyValue, Error> {
+        let name = name.to_string();
+        self.with_element(node, move |element, _| {
+            property::get_property(element, &name)
+        })?
+    }
+
+    /// Set the property `name` of the element `node`, also while the pipeline is running if the
+    /// property allows it.
+    pub fn set_property(
+        &mut self,
+        node: NodeId,
+        name: &str,
+        value: impl Into<PropertyValue>,
+    ) -> Result<(), Error> {
+        let (name, value) = (name.to_string(), value.into());
+        self.with_element(node, move |element, state| {
+            property::set_property(element, state, &name, value)
+        })?
+    }
+
+    /// Let every source that has not finished produce once and wait until they are done.
+    /// Returns the error that made the pipeline stop if an element failed, see
+    /// [`Error::root_cause`].
+    pub fn iter(&mut self) -> Result<(), Error> {
+        if self.state != State::Playing {
+            return Err(Error::PipelineNotPlaying);
+        }
+        let msg_receiver = self.msg_receiver.clone().ok_or(Error::PipelineNotReady)?;
+
+        let mut pending = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !node.is_src || node.finished {
+                continue;
+            }
+            if let Some(control) = &node.control {
+                control
+                    .send(Message::Iter)
+                    .map_err(|_| Error::MessageSinkFailed)?;
+                pending.push(NodeId(i));
+            }
+        }
+
+        if pending.is_empty() {
+            return Err(self.failure().unwrap_or(Error::PipelineFinished));
+        }
+
+        while !pending.is_empty() {
+            let (node, msg) = msg_receiver
                 .recv()
-                .map_err(|_| Error::ReceiveFromSinkFailed)?
-            {
-                Message::IterFin => Ok(()),
+                .map_err(|_| Error::ReceiveFromSinkFailed)?;
+            match msg {
+                Message::IterFin => {}
                 Message::Finished => {
                     debug!("Finished");
-                    Ok(())
+                    self.finish(node);
                 }
-                _ => Err(Error::ReceivedInvalidDatagramFromSink),
+                _ => return Err(Error::ReceivedInvalidDatagramFromSink),
             }
-        } else {
-            Err(Error::NoSinkMessageReceiver)
+            pending.retain(|n| *n != node);
         }
+
+        self.failure().map_or(Ok(()), Err)
     }
 }
 
 impl Drop for Pipeline {
     fn drop(&mut self) {
-        if let Err(e) = self.head.send_quit() {
-            error!("{}", e);
-        }
-
-        if let Err(e) = self.head.join_thread() {
-            error!("{}", e);
+        if let Err(e) = self.set_state(State::Null) {
+            error!("{e}");
         }
     }
 }
@@ -224,3 +1260,362 @@ impl Drop for Pipeline {
 define_log_info! {
     "pipeline"
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use crate::{
+        caps::{self, Structure, Value},
+        elements::{
+            conversion::bytes2text::Bytes2Text,
+            io::filesrc::FileSrc,
+            misc::{testsink::TestSink, testsrc::TestSrc},
+            text::{stdoutlog::StdoutLog, texttestsrc::TextTestSrc},
+        },
+        pipeline::event::Segment,
+    };
+
+    use super::*;
+
+    fn raw_audio(rate: Value) -> Caps {
+        Caps::new(Structure::new("audio/x-raw").field("rate", rate))
+    }
+
+    fn link(src_caps: Caps, sink_caps: Caps) -> Result<PipelineGraph, Error> {
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(src_caps, Vec::new()));
+        let sink = graph.add_element(TestSink::new(sink_caps, |_, _| true, |_, _| true));
+        graph.link((src, "src"), (sink, "sink"))?;
+        Ok(graph)
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let graph = link(
+            raw_audio(Value::List(vec![48000.into(), 44100.into()])),
+            raw_audio(Value::IntRange(8000, 44100)),
+        )
+        .unwrap();
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.init().unwrap();
+
+        let (_, caps) = &pipeline.negotiated_caps()[0];
+        assert!(caps.is_fixed());
+        assert_eq!(caps, &raw_audio(44100.into()));
+    }
+
+    #[test]
+    fn test_unlinked_sink_pad() {
+        let mut graph = PipelineGraph::new();
+        graph.add_element(TestSrc::new(caps::text_utf8(), Vec::new()));
+        graph.add_element(TestSink::default());
+        let mut pipeline = Pipeline::new(graph);
+
+        assert!(matches!(
+            pipeline.set_state(State::Paused),
+            Err(Error::UnlinkedSinkPad)
+        ));
+        pipeline.set_state(State::Null).unwrap();
+    }
+
+    #[test]
+    fn test_negotiation_fails() {
+        match link(
+            raw_audio(Value::List(vec![48000.into(), 44100.into()])),
+            raw_audio(Value::IntRange(8000, 32000)),
+        ) {
+            Err(Error::NoCommonFormat { src, sink, .. }) => {
+                assert_eq!(src, "testsrc0:src");
+                assert_eq!(sink, "testsink1:sink");
+            }
+            _ => panic!("Negotiation should fail"),
+        }
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let received = Arc::new(AtomicUsize::new(0));
+        let received_clone = Arc::clone(&received);
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![Datagram::Data(Data::Text(String::from("Hello")))],
+        ));
+        let sink = graph.add_element(TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, _| {
+                received_clone.fetch_add(1, Ordering::SeqCst);
+                true
+            },
+        ));
+        graph.link((src, "src"), (sink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.set_state(State::Paused).unwrap();
+        assert!(matches!(pipeline.iter(), Err(Error::PipelineNotPlaying)));
+
+        for _ in 0..2 {
+            pipeline.set_state(State::Playing).unwrap();
+            pipeline.iter().unwrap();
+            pipeline.set_state(State::Paused).unwrap();
+            pipeline.set_state(State::Playing).unwrap();
+            assert!(pipeline.iter().is_ok());
+            assert!(matches!(pipeline.iter(), Err(Error::PipelineFinished)));
+            // Restart from the beginning
+            pipeline.set_state(State::Ready).unwrap();
+        }
+
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+        pipeline.set_state(State::Null).unwrap();
+        assert_eq!(pipeline.state(), State::Null);
+    }
+
+    #[test]
+    fn test_free_running() {
+        let received = Arc::new(AtomicUsize::new(0));
+        let received_clone = Arc::clone(&received);
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            (0..3)
+                .map(|_| Datagram::Data(Data::Text(String::from("Hello"))))
+                .collect(),
+        ));
+        let sink = graph.add_element(TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, _| {
+                received_clone.fetch_add(1, Ordering::SeqCst);
+                true
+            },
+        ));
+        graph.link((src, "src"), (sink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        let bus = pipeline.bus();
+        assert!(matches!(
+            pipeline.wait_eos(),
+            Err(Error::PipelineNotFreeRunning)
+        ));
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            pipeline.link_stats()[0].1,
+            LinkStats {
+                buffers: 3,
+                bytes: 15
+            }
+        );
+        assert_eq!(pipeline.element_path(sink), Some("testsink1"));
+
+        let messages = bus
+            .try_iter()
+            .filter(|msg| !matches!(msg, BusMessage::StateChanged { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages.last(), Some(BusMessage::Eos)));
+    }
+
+    #[test]
+    fn test_free_running_paused() {
+        let received = Arc::new(AtomicUsize::new(0));
+        let received_clone = Arc::clone(&received);
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            (0..10_000)
+                .map(|_| Datagram::Data(Data::Text(String::from("Hello"))))
+                .collect(),
+        ));
+        let sink = graph.add_element(TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, _| {
+                received_clone.fetch_add(1, Ordering::SeqCst);
+                true
+            },
+        ));
+        graph.link((src, "src"), (sink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.play().unwrap();
+        pipeline.set_state(State::Paused).unwrap();
+        // The source waits for the pipeline instead of producing. The data that was in flight
+        // when pausing is given time to arrive first.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let paused_at = received.load(Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(received.load(Ordering::SeqCst), paused_at);
+
+        pipeline.set_state(State::Playing).unwrap();
+        pipeline.wait_eos().unwrap();
+        assert_eq!(received.load(Ordering::SeqCst), 10_000);
+    }
+
+    #[test]
+    fn test_bus_error() {
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TestSrc::new(
+            caps::text_utf8(),
+            vec![Datagram::Data(Data::Bytes(b"Not text".to_vec()))],
+        ));
+        let stdoutlog = graph.add_element(StdoutLog::new());
+        graph.link((src, "src"), (stdoutlog, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        let bus = pipeline.bus();
+        pipeline.play().unwrap();
+
+        let error = bus
+            .iter()
+            .find_map(|msg| match msg {
+                BusMessage::Error { source, error } => Some((source, error)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(error.0, "stdoutlog1");
+        assert!(matches!(*error.1, Error::ReceivedInvalidDatagramFromParent));
+        assert!(bus
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap()
+            .is_some());
+
+        let error = pipeline.wait_eos().unwrap_err();
+        assert!(matches!(&error, Error::Element { path, .. } if path == "stdoutlog1"));
+        assert!(!bus.try_iter().any(|msg| matches!(msg, BusMessage::Eos)));
+        assert!(matches!(
+            error.root_cause(),
+            Error::ReceivedInvalidDatagramFromParent
+        ));
+    }
+
+    #[test]
+    fn test_query() {
+        let file = std::fs::File::open("README.md").unwrap();
+        let size = file.metadata().unwrap().len();
+        let mut graph = PipelineGraph::new();
+        let filesrc = graph.add_element(FileSrc::new(file));
+        let bytes2text = graph.add_element(Bytes2Text::new());
+        let sink = graph.add_element(TestSink::new(caps::text_utf8(), |_, _| true, |_, _| true));
+        graph.link((filesrc, "src"), (bytes2text, "sink")).unwrap();
+        graph.link((bytes2text, "src"), (sink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        assert!(matches!(
+            pipeline.query(Query::latency()),
+            Err(Error::PipelineNotReady)
+        ));
+        pipeline.set_state(State::Paused).unwrap();
+
+        assert_eq!(pipeline.query_duration(Format::Bytes).unwrap(), Some(size));
+        assert_eq!(pipeline.query_position(Format::Bytes).unwrap(), Some(0));
+        assert_eq!(pipeline.query_duration(Format::Time).unwrap(), None);
+        assert_eq!(
+            pipeline.query(Query::caps(Caps::any())).unwrap(),
+            Some(Query::Caps {
+                filter: Caps::any(),
+                caps: caps::text_utf8(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_seek() {
+        let contents = std::fs::read_to_string("README.md").unwrap();
+        let texts = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (texts_clone, events_clone) = (Arc::clone(&texts), Arc::clone(&events));
+        let mut graph = PipelineGraph::new();
+        let filesrc = graph.add_element(FileSrc::new(std::fs::File::open("README.md").unwrap()));
+        let bytes2text = graph.add_element(Bytes2Text::new());
+        let mut testsink = TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, data| {
+                texts_clone.lock().unwrap().push(data);
+                true
+            },
+        );
+        testsink.set_on_event(move |event| events_clone.lock().unwrap().push(event));
+        let sink = graph.add_element(testsink);
+        graph.link((filesrc, "src"), (bytes2text, "sink")).unwrap();
+        graph.link((bytes2text, "src"), (sink, "sink")).unwrap();
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.init().unwrap();
+        pipeline.iter().unwrap();
+        assert!(!pipeline.seek(Duration::ZERO, SeekFlags::default()).unwrap());
+        assert!(pipeline.seek_bytes(2, SeekFlags::default()).unwrap());
+        pipeline.iter().unwrap();
+        drop(pipeline);
+
+        assert_eq!(
+            *texts.lock().unwrap(),
+            vec![
+                Data::Text(contents.clone()),
+                Data::Text(contents[2..].to_string())
+            ]
+        );
+        assert_eq!(
+            events.lock().unwrap()[2..],
+            [
+                Event::FlushStart,
+                Event::FlushStop,
+                Event::Segment(Segment::bytes(2, Some(contents.len() as u64)))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_properties() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = Arc::clone(&received);
+        let mut graph = PipelineGraph::new();
+        let src = graph.add_element(TextTestSrc::new());
+        let sink = graph.add_element(TestSink::new(
+            caps::text_utf8(),
+            |_, _| true,
+            move |_, data| {
+                received_clone.lock().unwrap().push(data);
+                true
+            },
+        ));
+        graph.link((src, "src"), (sink, "sink")).unwrap();
+        graph.set_property(src, "text", "Hello").unwrap();
+        assert!(matches!(
+            graph.set_property(src, "text", 42),
+            Err(Error::InvalidPropertyValue(_))
+        ));
+        assert!(matches!(
+            graph.set_property(src, "size", 42),
+            Err(Error::NoSuchProperty(_))
+        ));
+
+        let mut pipeline = Pipeline::new(graph);
+        pipeline.init().unwrap();
+        pipeline.iter().unwrap();
+        // The source is running in its own thread now
+        pipeline.set_property(src, "text", "World").unwrap();
+        assert_eq!(
+            pipeline.property(src, "text").unwrap(),
+            PropertyValue::Str(String::from("World"))
+        );
+        pipeline.iter().unwrap();
+        assert_eq!(pipeline.properties(sink).unwrap(), Vec::new());
+        drop(pipeline);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Data::Text(String::from("Hello")),
+                Data::Text(String::from("World")),
+            ]
+        );
+    }
+}
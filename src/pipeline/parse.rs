@@ -0,0 +1,437 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Build pipelines from text descriptions.
+//!
+//! Elements are created by name from the [global registry](crate::registry) and linked with
+//! `!`. Properties are set with `property=value`, values with spaces can be quoted with `"`.
+//!
+//! An element can be given a name with `name=...` and referred to later with `name.`, or
+//! `name.pad` for a specific pad. A reference that is not preceded by `!` starts a new branch:
+//!
+//!```text
+//!texttestsrc text=Hello ! tee name=t  t. ! queue ! stdoutlog  t. ! queue ! stdoutlog
+//!```
+//!
+//! When no pad is given, the first pad that can be linked is used. Links are negotiated when
+//! the pipeline is started.
+//!
+//! # Example
+//! ```
+//!use streamcraft::pipeline::parse::parse_launch;
+//!
+//!let mut pipeline = parse_launch("filesrc location=README.md ! bytes2text ! stdoutlog").unwrap();
+//!pipeline.play().unwrap();
+//!pipeline.wait_eos().unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+use crate::registry;
+
+use super::{
+    error::Error,
+    graph::{NodeId, PipelineGraph},
+    Pipeline,
+};
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    UnterminatedQuote,
+    UnexpectedToken,
+    ExpectedElement,
+    ExpectedValue,
+    /// A property that does not follow an element, e.g. after a reference.
+    PropertyWithoutElement,
+    DuplicateName(String),
+    NoSuchName(String),
+    /// Creating, configuring or linking an element failed.
+    Pipeline(Box<Error>),
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::UnterminatedQuote => "Unterminated quote".to_string(),
+                Self::UnexpectedToken => "Unexpected token".to_string(),
+                Self::ExpectedElement => "Expected an element".to_string(),
+                Self::ExpectedValue => "Expected a value".to_string(),
+                Self::PropertyWithoutElement =>
+                    "Property does not belong to an element".to_string(),
+                Self::DuplicateName(name) => format!("Name {name} is used more than once"),
+                Self::NoSuchName(name) => format!("No element is named {name}"),
+                Self::Pipeline(e) => e.to_string(),
+            }
+        )
+    }
+}
+
+/// What went wrong and where in the description, as a byte range.
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    /// Show `description` with the part that is wrong marked below it.
+    pub fn highlight(&self, description: &str) -> String {
+        let start = description[..self.span.start.min(description.len())]
+            .chars()
+            .count();
+        let len = description
+            .get(self.span.clone())
+            .map(|s| s.chars().count())
+            .unwrap_or(0)
+            .max(1);
+        format!("{description}\n{}{}", " ".repeat(start), "^".repeat(len))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind, self.span.start)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Eq,
+    Link,
+}
+
+fn tokenize(description: &str) -> Result<Vec<(Token, Range<usize>)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = description.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '!' => tokens.push((Token::Link, start..start + 1)),
+            '=' => tokens.push((Token::Eq, start..start + 1)),
+            '"' => {
+                let mut word = String::new();
+                let end = loop {
+                    match chars.next() {
+                        Some((i, '"')) => break i + 1,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => word.push(c),
+                            None => break description.len() + 1,
+                        },
+                        Some((_, c)) => word.push(c),
+                        None => break description.len() + 1,
+                    }
+                };
+                if end > description.len() {
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnterminatedQuote,
+                        start..start + 1,
+                    ));
+                }
+                tokens.push((Token::Word(word), start..end));
+            }
+            c => {
+                let mut word = String::from(c);
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !matches!(c, '!' | '=' | '"'))
+                {
+                    word.push(c);
+                    end = i + c.len_utf8();
+                }
+                tokens.push((Token::Word(word), start..end));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// One side of a link.
+#[derive(Clone)]
+enum Endpoint {
+    Element(NodeId),
+    /// `name.pad`, the pad is empty if it was not given.
+    Reference(String, String),
+}
+
+/// Find the node and pad an endpoint refers to.
+fn resolve(
+    endpoint: &Endpoint,
+    names: &HashMap<String, NodeId>,
+    span: &Range<usize>,
+) -> Result<(NodeId, Option<String>), ParseError> {
+    match endpoint {
+        Endpoint::Element(node) => Ok((*node, None)),
+        Endpoint::Reference(name, pad) => names
+            .get(name)
+            .map(|node| (*node, (!pad.is_empty()).then(|| pad.clone())))
+            .ok_or_else(|| ParseError::new(ParseErrorKind::NoSuchName(name.clone()), span.clone())),
+    }
+}
+
+/// Link `src` to `sink`, using the first pads that can be linked if no pad was given.
+fn link(
+    graph: &mut PipelineGraph,
+    (src, src_pad): (NodeId, Option<String>),
+    (sink, sink_pad): (NodeId, Option<String>),
+) -> Result<(), Error> {
+    let src_pads = match src_pad {
+        Some(pad) => vec![pad],
+        None => graph
+            .architecture(src)?
+            .srcs
+            .into_iter()
+            .map(|template| template.name)
+            .collect(),
+    };
+    let sink_pads = match sink_pad {
+        Some(pad) => vec![pad],
+        None => graph
+            .architecture(sink)?
+            .sinks
+            .into_iter()
+            .map(|template| template.name)
+            .collect(),
+    };
+
+    let mut error = Error::NoSuchPad;
+    for src_pad in &src_pads {
+        for sink_pad in &sink_pads {
+            match graph.link((src, src_pad), (sink, sink_pad)) {
+                Ok(()) => return Ok(()),
+                // Not being able to agree on a format says more than pads that do not fit
+                Err(e @ Error::NoCommonFormat { .. }) => error = e,
+                Err(e) if !matches!(error, Error::NoCommonFormat { .. }) => error = e,
+                Err(_) => {}
+            }
+        }
+    }
+
+    Err(error)
+}
+
+/// Build a graph from `description`.
+pub fn parse_graph(description: &str) -> Result<PipelineGraph, ParseError> {
+    let mut graph = PipelineGraph::new();
+    let mut names = HashMap::new();
+    let mut links = Vec::new();
+    // The last element or reference, and whether a `!` follows it
+    let mut previous: Option<(Endpoint, Range<usize>)> = None;
+    let mut link_span: Option<Range<usize>> = None;
+    // The element properties are set on
+    let mut current: Option<NodeId> = None;
+
+    let tokens = tokenize(description)?;
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some((token, span)) = tokens.next() {
+        match token {
+            Token::Word(property)
+                if tokens.peek().is_some_and(|(token, _)| *token == Token::Eq) =>
+            {
+                let eq_span = tokens.next().map(|(_, span)| span).unwrap_or(span.clone());
+                let (value, value_span) = match tokens.next() {
+                    Some((Token::Word(value), value_span)) => (value, value_span),
+                    _ => {
+                        return Err(ParseError::new(ParseErrorKind::ExpectedValue, eq_span));
+                    }
+                };
+                let node = current.ok_or_else(|| {
+                    ParseError::new(ParseErrorKind::PropertyWithoutElement, span.clone())
+                })?;
+
+                if property == "name" {
+                    if names.insert(value.clone(), node).is_some() {
+                        return Err(ParseError::new(
+                            ParseErrorKind::DuplicateName(value),
+                            value_span,
+                        ));
+                    }
+                    continue;
+                }
+
+                let spec = graph
+                    .properties(node)
+                    .ok()
+                    .and_then(|specs| specs.into_iter().find(|spec| spec.name == property))
+                    .ok_or_else(|| {
+                        ParseError::new(
+                            ParseErrorKind::Pipeline(Box::new(Error::NoSuchProperty(
+                                property.clone(),
+                            ))),
+                            span.clone(),
+                        )
+                    })?;
+                spec.parse(&value)
+                    .and_then(|value| graph.set_property(node, &property, value))
+                    .map_err(|e| {
+                        ParseError::new(ParseErrorKind::Pipeline(Box::new(e)), value_span)
+                    })?;
+            }
+            Token::Word(word) => {
+                let endpoint = match word.split_once('.') {
+                    Some((name, pad)) => {
+                        current = None;
+                        Endpoint::Reference(name.to_string(), pad.to_string())
+                    }
+                    None => {
+                        let element = registry::make_element(&word, &[]).map_err(|e| {
+                            ParseError::new(ParseErrorKind::Pipeline(Box::new(e)), span.clone())
+                        })?;
+                        let node = graph.add_boxed_element(element);
+                        current = Some(node);
+                        Endpoint::Element(node)
+                    }
+                };
+
+                if let Some(link_span) = link_span.take() {
+                    if let Some((src, src_span)) = previous.take() {
+                        links.push((src, src_span, endpoint.clone(), span.clone(), link_span));
+                    }
+                }
+                previous = Some((endpoint, span));
+            }
+            Token::Link => {
+                if previous.is_none() || link_span.is_some() {
+                    return Err(ParseError::new(ParseErrorKind::ExpectedElement, span));
+                }
+                current = None;
+                link_span = Some(span);
+            }
+            Token::Eq => return Err(ParseError::new(ParseErrorKind::UnexpectedToken, span)),
+        }
+    }
+
+    if previous.is_none() || link_span.is_some() {
+        let end = description.len();
+        return Err(ParseError::new(ParseErrorKind::ExpectedElement, end..end));
+    }
+
+    // Links are made last so references can refer to elements that are named later
+    for (src, src_span, sink, sink_span, link_span) in links {
+        let src = resolve(&src, &names, &src_span)?;
+        let sink = resolve(&sink, &names, &sink_span)?;
+        link(&mut graph, src, sink)
+            .map_err(|e| ParseError::new(ParseErrorKind::Pipeline(Box::new(e)), link_span))?;
+    }
+
+    Ok(graph)
+}
+
+/// Build a pipeline from `description`.
+pub fn parse_launch(description: &str) -> Result<Pipeline, ParseError> {
+    Ok(Pipeline::new(parse_graph(description)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_kind(description: &str) -> (ParseErrorKind, Range<usize>) {
+        match parse_graph(description) {
+            Ok(_) => panic!("{description} parsed"),
+            Err(e) => (e.kind, e.span),
+        }
+    }
+
+    fn pipeline_error(description: &str) -> (Error, Range<usize>) {
+        match error_kind(description) {
+            (ParseErrorKind::Pipeline(e), span) => (*e, span),
+            (kind, _) => panic!("{description} failed with {kind}"),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let graph = parse_graph(
+            "texttestsrc text=\"Hello, World!\" ! tee name=t \
+             t. ! queue max-buffers=2 ! testsink \
+             t.src_%u ! testsink",
+        )
+        .unwrap();
+        assert_eq!(graph.edges().len(), 4);
+        assert_eq!(
+            graph.property(NodeId(0), "text").unwrap(),
+            "Hello, World!".into()
+        );
+
+        let mut pipeline =
+            parse_launch("filesrc location=README.md ! bytes2text ! testsink").unwrap();
+        pipeline.play().unwrap();
+        pipeline.wait_eos().unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            error_kind("filesrc ! ! testsink"),
+            (
+                ParseErrorKind::ExpectedElement,
+                Range { start: 10, end: 11 }
+            )
+        ));
+        assert!(matches!(
+            pipeline_error("nosuchelement ! testsink"),
+            (Error::NoSuchElement(_), Range { start: 0, end: 13 })
+        ));
+        assert!(matches!(
+            error_kind("filesrc location=\"README.md ! testsink"),
+            (
+                ParseErrorKind::UnterminatedQuote,
+                Range { start: 17, end: 18 }
+            )
+        ));
+        assert!(matches!(
+            pipeline_error("filesrc block-size=zero ! testsink"),
+            (Error::InvalidPropertyValue(_), Range { start: 19, end: 23 })
+        ));
+        assert!(matches!(
+            error_kind("texttestsrc ! t."),
+            (ParseErrorKind::NoSuchName(_), Range { start: 14, end: 16 })
+        ));
+        assert!(matches!(
+            pipeline_error("texttestsrc ! bytes2text"),
+            (Error::NoCommonFormat { .. }, Range { start: 12, end: 13 })
+        ));
+        assert!(matches!(
+            error_kind("texttestsrc !"),
+            (
+                ParseErrorKind::ExpectedElement,
+                Range { start: 13, end: 13 }
+            )
+        ));
+
+        let Err(e) = parse_graph("filesrc ! ! testsink") else {
+            panic!("Parsed an invalid description");
+        };
+        assert_eq!(
+            e.highlight("filesrc ! ! testsink"),
+            "filesrc ! ! testsink\n          ^"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use crate::caps::Caps;
+
+/// The unit of positions and durations in a [`Query`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Format {
+    /// Nanoseconds.
+    #[default]
+    Time,
+    Bytes,
+}
+
+/// A question to an element, answered by filling in the fields that are `None` or empty.
+///
+/// Queries that an element does not answer are forwarded through its pads, upstream if the
+/// query arrived on a src pad and downstream if it arrived on a sink pad.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Query {
+    Position {
+        format: Format,
+        position: Option<u64>,
+    },
+    Duration {
+        format: Format,
+        duration: Option<u64>,
+    },
+    /// Whether the stream can be seeked and the range that can be seeked in.
+    Seeking {
+        format: Format,
+        seekable: bool,
+        start: u64,
+        end: Option<u64>,
+    },
+    Latency {
+        live: bool,
+        min: Duration,
+        max: Option<Duration>,
+    },
+    /// The caps a pad can handle, intersected with `filter`.
+    Caps { filter: Caps, caps: Caps },
+    /// How large the buffers downstream wants are and how many it needs.
+    Allocation {
+        caps: Caps,
+        size: usize,
+        min_buffers: usize,
+    },
+}
+
+impl Query {
+    pub fn position(format: Format) -> Self {
+        Self::Position {
+            format,
+            position: None,
+        }
+    }
+
+    pub fn duration(format: Format) -> Self {
+        Self::Duration {
+            format,
+            duration: None,
+        }
+    }
+
+    pub fn seeking(format: Format) -> Self {
+        Self::Seeking {
+            format,
+            seekable: false,
+            start: 0,
+            end: None,
+        }
+    }
+
+    pub fn latency() -> Self {
+        Self::Latency {
+            live: false,
+            min: Duration::ZERO,
+            max: None,
+        }
+    }
+
+    pub fn caps(filter: Caps) -> Self {
+        Self::Caps {
+            filter,
+            caps: Caps::empty(),
+        }
+    }
+
+    pub fn allocation(caps: Caps) -> Self {
+        Self::Allocation {
+            caps,
+            size: 0,
+            min_buffers: 0,
+        }
+    }
+}
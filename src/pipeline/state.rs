@@ -0,0 +1,96 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::{self, Display, Formatter};
+
+/// The state of a pipeline and its elements.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum State {
+    /// Nothing is allocated and no threads are running.
+    Null,
+    /// Elements have opened their resources, e.g. files and demuxers.
+    Ready,
+    /// Links are negotiated and every element is running in its thread, but no data flows.
+    Paused,
+    /// Data flows.
+    Playing,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Null => "Null",
+                Self::Ready => "Ready",
+                Self::Paused => "Paused",
+                Self::Playing => "Playing",
+            }
+        )
+    }
+}
+
+/// A transition between two neighbouring states.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StateChange {
+    NullToReady,
+    ReadyToPaused,
+    PausedToPlaying,
+    PlayingToPaused,
+    PausedToReady,
+    ReadyToNull,
+}
+
+impl StateChange {
+    /// Get the first transition on the way from `current` to `target`.
+    pub fn towards(current: State, target: State) -> Option<StateChange> {
+        use State::*;
+
+        Some(match (current, target) {
+            (Null, Ready | Paused | Playing) => Self::NullToReady,
+            (Ready, Paused | Playing) => Self::ReadyToPaused,
+            (Paused, Playing) => Self::PausedToPlaying,
+            (Playing, Paused | Ready | Null) => Self::PlayingToPaused,
+            (Paused, Ready | Null) => Self::PausedToReady,
+            (Ready, Null) => Self::ReadyToNull,
+            _ => return None,
+        })
+    }
+
+    pub fn current(&self) -> State {
+        match self {
+            Self::NullToReady => State::Null,
+            Self::ReadyToPaused | Self::ReadyToNull => State::Ready,
+            Self::PausedToPlaying | Self::PausedToReady => State::Paused,
+            Self::PlayingToPaused => State::Playing,
+        }
+    }
+
+    pub fn next(&self) -> State {
+        match self {
+            Self::ReadyToNull => State::Null,
+            Self::NullToReady | Self::PausedToReady => State::Ready,
+            Self::ReadyToPaused | Self::PlayingToPaused => State::Paused,
+            Self::PausedToPlaying => State::Playing,
+        }
+    }
+}
+
+impl Display for StateChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.current(), self.next())
+    }
+}
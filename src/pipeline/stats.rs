@@ -0,0 +1,46 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::Data;
+
+/// How much data went over a link. Events and messages are not counted.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct LinkStats {
+    pub buffers: u64,
+    pub bytes: u64,
+}
+
+/// Counts the data received on a sink pad while the pipeline reads the count from another thread.
+#[derive(Default)]
+pub(crate) struct LinkCounter {
+    buffers: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl LinkCounter {
+    pub(crate) fn count(&self, data: &Data) {
+        self.buffers.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(data.size() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> LinkStats {
+        LinkStats {
+            buffers: self.buffers.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Raw video.
+
+use std::time::Duration;
+
+use crate::caps::{self, Caps, Structure};
+
+/// The format of raw video.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct VideoFormat {
+    /// Name of the libav pixel format, e.g. `yuv420p`.
+    pub pixel_format: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl VideoFormat {
+    /// Fixed [`caps::VIDEO_RAW`] caps for this format.
+    pub fn caps(&self) -> Caps {
+        Caps::new(
+            Structure::new(caps::VIDEO_RAW)
+                .field("format", self.pixel_format.as_str())
+                .field("width", self.width)
+                .field("height", self.height),
+        )
+    }
+
+    /// The format in fixed [`caps::VIDEO_RAW`] caps, `None` if the caps are not raw video or
+    /// a field is missing.
+    pub fn from_caps(caps: &Caps) -> Option<Self> {
+        let structure = caps.structure()?;
+        if structure.name != caps::VIDEO_RAW {
+            return None;
+        }
+
+        Some(Self {
+            pixel_format: structure.get_str("format")?.to_string(),
+            width: structure.get_int("width")?.try_into().ok()?,
+            height: structure.get_int("height")?.try_into().ok()?,
+        })
+    }
+}
+
+/// One plane of a [`VideoFrame`], e.g. the luma of a YUV frame.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct VideoPlane {
+    pub data: Vec<u8>,
+    /// Bytes from the start of one line to the start of the next, can be more than the width.
+    pub stride: usize,
+}
+
+/// A decoded video frame.
+#[derive(PartialEq, Clone, Debug)]
+pub struct VideoFrame {
+    pub format: VideoFormat,
+    pub planes: Vec<VideoPlane>,
+    /// Presentation time, `None` if unknown.
+    pub pts: Option<Duration>,
+    /// `None` if unknown.
+    pub duration: Option<Duration>,
+    pub keyframe: bool,
+    pub interlaced: bool,
+    /// Whether the top field is displayed first, only meaningful for interlaced frames.
+    pub top_field_first: bool,
+}
+
+impl VideoFrame {
+    /// Size of the data of all planes in bytes.
+    pub fn size(&self) -> usize {
+        self.planes.iter().map(|plane| plane.data.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_caps() {
+        let format = VideoFormat {
+            pixel_format: "yuv420p".to_string(),
+            width: 1920,
+            height: 1080,
+        };
+
+        assert!(format.caps().is_fixed());
+        assert_eq!(VideoFormat::from_caps(&format.caps()), Some(format));
+        assert_eq!(VideoFormat::from_caps(&caps::video_raw()), None);
+        assert_eq!(VideoFormat::from_caps(&caps::text_utf8()), None);
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (C) 2024  MAlba124 <marlhan@proton.me>
+//
+// StreamCraft is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// StreamCraft is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with StreamCraft.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Create elements by name.
+//!
+//! Every element that is enabled with its cargo feature, e.g. `element-filesrc`, is in the global
+//! registry. Elements that need arguments to be created, like `testsrc`, are not.
+//!
+//! # Example
+//! ```
+//!use streamcraft::{
+//!    pipeline::{graph::PipelineGraph, Pipeline},
+//!    registry,
+//!};
+//!
+//!let mut graph = PipelineGraph::new();
+//!let filesrc = graph.add_boxed_element(
+//!    registry::make_element("filesrc", &[("location", "README.md".into())]).unwrap(),
+//!);
+//!let bytes2text = graph.add_boxed_element(registry::make_element("bytes2text", &[]).unwrap());
+//!let stdoutlog = graph.add_boxed_element(registry::make_element("stdoutlog", &[]).unwrap());
+//!graph.link((filesrc, "src"), (bytes2text, "sink")).unwrap();
+//!graph.link((bytes2text, "src"), (stdoutlog, "sink")).unwrap();
+//!
+//!let mut pipeline = Pipeline::new(graph);
+//!pipeline.play().unwrap();
+//!pipeline.wait_eos().unwrap();
+//! ```
+
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use crate::{
+    element_traits::{
+        property::{self, PropertySpec, PropertyValue},
+        Element, ElementArchitecture,
+    },
+    elements,
+    pipeline::{error::Error, state::State},
+};
+
+/// Creates one kind of element and describes it.
+#[derive(Clone)]
+pub struct ElementFactory {
+    pub name: &'static str,
+    /// Slash separated categories, e.g. `Source/File`.
+    pub classification: &'static str,
+    pub description: &'static str,
+    create: fn() -> Box<dyn Element>,
+}
+
+impl ElementFactory {
+    pub fn new(
+        name: &'static str,
+        classification: &'static str,
+        description: &'static str,
+        create: fn() -> Box<dyn Element>,
+    ) -> Self {
+        Self {
+            name,
+            classification,
+            description,
+            create,
+        }
+    }
+
+    /// Create the element with its default settings.
+    pub fn create(&self) -> Box<dyn Element> {
+        (self.create)()
+    }
+
+    /// The pad templates of the element.
+    pub fn architecture(&self) -> ElementArchitecture {
+        self.create().get_architecture()
+    }
+
+    pub fn properties(&self) -> Vec<PropertySpec> {
+        self.create().properties()
+    }
+}
+
+/// Element factories by name.
+#[derive(Default)]
+pub struct ElementRegistry {
+    /// Sorted by name.
+    factories: Vec<ElementFactory>,
+}
+
+impl ElementRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with every element that is enabled with its cargo feature.
+    pub fn with_elements() -> Self {
+        let mut registry = Self::new();
+        elements::register(&mut registry);
+        registry
+    }
+
+    /// Add `factory`, replacing any factory with the same name.
+    pub fn register(&mut self, factory: ElementFactory) {
+        match self
+            .factories
+            .binary_search_by(|other| other.name.cmp(factory.name))
+        {
+            Ok(index) => self.factories[index] = factory,
+            Err(index) => self.factories.insert(index, factory),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ElementFactory> {
+        self.factories.iter().find(|factory| factory.name == name)
+    }
+
+    /// All factories, sorted by name.
+    pub fn factories(&self) -> &[ElementFactory] {
+        &self.factories
+    }
+
+    /// Create the element named `name` and set `properties` on it.
+    pub fn make_element(
+        &self,
+        name: &str,
+        properties: &[(&str, PropertyValue)],
+    ) -> Result<Box<dyn Element>, Error> {
+        let factory = self
+            .find(name)
+            .ok_or_else(|| Error::NoSuchElement(name.to_string()))?;
+        let mut element = factory.create();
+        for (name, value) in properties {
+            property::set_property(element.as_mut(), State::Null, name, value.clone())?;
+        }
+
+        Ok(element)
+    }
+}
+
+/// The global registry, which starts out with [`ElementRegistry::with_elements`].
+pub fn global() -> &'static RwLock<ElementRegistry> {
+    static REGISTRY: OnceLock<RwLock<ElementRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(ElementRegistry::with_elements()))
+}
+
+/// Add `factory` to the global registry.
+pub fn register(factory: ElementFactory) {
+    global()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register(factory);
+}
+
+/// Find the factory named `name` in the global registry.
+pub fn find(name: &str) -> Option<ElementFactory> {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .find(name)
+        .cloned()
+}
+
+/// All factories in the global registry, sorted by name.
+pub fn factories() -> Vec<ElementFactory> {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .factories()
+        .to_vec()
+}
+
+/// Create the element named `name` from the global registry and set `properties` on it.
+pub fn make_element(
+    name: &str,
+    properties: &[(&str, PropertyValue)],
+) -> Result<Box<dyn Element>, Error> {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .make_element(name, properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "element-filesrc")]
+    #[test]
+    fn test_make_element() {
+        let registry = ElementRegistry::with_elements();
+        assert!(registry
+            .factories()
+            .windows(2)
+            .all(|pair| pair[0].name < pair[1].name));
+
+        let filesrc = registry
+            .make_element("filesrc", &[("block-size", 42.into())])
+            .unwrap();
+        assert_eq!(filesrc.name(), "filesrc");
+        assert_eq!(filesrc.property("block-size"), Some(PropertyValue::Int(42)));
+        assert!(matches!(
+            registry.make_element("nosuchelement", &[]),
+            Err(Error::NoSuchElement(_))
+        ));
+        assert!(matches!(
+            registry.make_element("filesrc", &[("block-size", 0.into())]),
+            Err(Error::InvalidPropertyValue(_))
+        ));
+    }
+
+    #[cfg(feature = "element-demuxsrc")]
+    #[test]
+    fn test_make_av_element() {
+        let registry = ElementRegistry::with_elements();
+        // The file is opened later, in Ready
+        let demuxsrc = registry.make_element("demuxsrc", &[]).unwrap();
+        assert_eq!(
+            demuxsrc.property("location"),
+            Some(PropertyValue::Str(String::new()))
+        );
+        assert!(registry.make_element("videodecoder", &[]).is_ok());
+        assert!(registry.make_element("audiodecoder", &[]).is_ok());
+    }
+}